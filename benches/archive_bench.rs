@@ -14,5 +14,43 @@ fn criterion_benchmark(c: &mut Criterion) {
     // });
 }
 
-criterion_group!(benches, criterion_benchmark);
+/// Режимы заполненного словаря: сброс, вытеснение строк (LZT) и сброс
+/// по коду CLEAR на маленьком словаре, который заполняется много раз.
+/// Размер сжатых данных для сравнения выводится перед замерами
+fn dictionary_mode_benchmark(c: &mut Criterion) {
+    let source = std::fs::read("test-file").unwrap();
+    let modes = vec![
+        ("reset", lzw::DictionaryMode::Reset),
+        ("lzt", lzw::DictionaryMode::Lzt),
+        ("clear", lzw::DictionaryMode::Clear),
+    ];
+    for (name, mode) in &modes {
+        let options = lzw::Options {
+            max_bits_count: 12,
+            dictionary_mode: *mode,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        lzw::compress_bytes_with(&source, &options, &mut out).unwrap();
+        println!("{}: {} -> {} bytes", name, source.len(), out.len());
+    }
+    c.bench_function_over_inputs(
+        "compress 12 bits",
+        move |b, &(_, mode)| {
+            let options = lzw::Options {
+                max_bits_count: 12,
+                dictionary_mode: mode,
+                ..Default::default()
+            };
+            let mut out = Vec::new();
+            b.iter(|| {
+                out.clear();
+                lzw::compress_bytes_with(&source, &options, &mut out).unwrap()
+            })
+        },
+        modes,
+    );
+}
+
+criterion_group!(benches, criterion_benchmark, dictionary_mode_benchmark);
 criterion_main!(benches);
@@ -0,0 +1,45 @@
+use criterion::*;
+use lzw_arc::lzw::bits::{BitOrder, BitReader, BitWriter};
+
+/// Количество кодов в одном замере
+const COUNT: u32 = 1 << 20;
+/// Длина кода: типичная для словаря на 64K строк
+const BITS: u8 = 16;
+
+fn encode(order: BitOrder) -> Vec<u8> {
+    let mut writer = BitWriter::new(order);
+    let mut out = Vec::with_capacity(COUNT as usize * BITS as usize / 8);
+    for code in 0..COUNT {
+        writer.write_bits(&mut out, code, BITS).unwrap();
+    }
+    writer.pad(&mut out).unwrap();
+    out
+}
+fn decode(order: BitOrder, data: &[u8]) -> u32 {
+    let mut reader = BitReader::new(order);
+    let mut data = data;
+    let mut sum = 0u32;
+    while let Some(code) = reader.read_bits(&mut data, BITS).unwrap() {
+        sum = sum.wrapping_add(code);
+    }
+    sum
+}
+
+/// Упаковка и распаковка 1M кодов в обоих порядках бит
+fn bits_benchmark(c: &mut Criterion) {
+    let orders = vec![("msb", BitOrder::Msb), ("lsb", BitOrder::Lsb)];
+    c.bench_function_over_inputs(
+        "write 1M codes",
+        |b, &(_, order)| b.iter(|| encode(order)),
+        orders.clone(),
+    );
+    for (name, order) in orders {
+        let data = encode(order);
+        c.bench_function(&format!("read 1M codes/{}", name), move |b| {
+            b.iter(|| decode(order, &data))
+        });
+    }
+}
+
+criterion_group!(benches, bits_benchmark);
+criterion_main!(benches);
@@ -1,28 +1,80 @@
-use clap::{crate_version, App, Arg};
-use lzw_arc::lzw;
-fn main() -> std::io::Result<()> {
+use clap::{crate_version, value_t, App, Arg, ErrorKind};
+use lzw_arc::lzw::archive::{self, ArchiveReader, ArchiveWriter, EntryKind};
+use lzw_arc::lzw::lzc::{self, ZDecoder, ZEncoder};
+use lzw_arc::lzw::{self, header::Header};
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Имя файла, обозначающее стандартный ввод или вывод
+const STDIO: &str = "-";
+fn main() {
+    match run() {
+        Ok(true) => {}
+        // Архив не прошел проверку: отдельный код, чтобы скрипты
+        // могли отличить его от прочих ошибок
+        Ok(false) => std::process::exit(2),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+fn run() -> lzw::Result<bool> {
     let matches = App::new("LZW Archiver")
         .version(crate_version!())
         .author("Dmitriy H. <alukard.develop@gmail.com>")
         .about("lzw file archiver with aes encryption")
+        .after_help(
+            "EXIT STATUS:\n    0    success\n    1    usage or I/O error, damaged archive\n    \
+             2    archive failed the integrity test (t)",
+        )
         .arg(
             Arg::with_name("mode")
-                .help("a for compress and e for extract")
+                .help("a to compress, e to extract, l to list contents, t to test integrity")
                 .index(1)
-                .possible_values(&["a", "e"])
+                .possible_values(&["a", "e", "l", "t"])
                 .required(true),
         )
-        .arg(Arg::with_name("input_file").index(2).required(true))
-        .arg(Arg::with_name("result_file").index(3).required(true))
+        .arg(
+            Arg::with_name("archive")
+                .help("archive file, - for stdin (e, l, t) or stdout (a)")
+                .index(2)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("paths")
+                .help(
+                    "files and directories to add (a), or destination (e); \
+                     - compresses stdin into a single stream or extracts one to stdout",
+                )
+                .index(3)
+                .multiple(true)
+                .required_if("mode", "a"),
+        )
         .arg(
             Arg::with_name("bits_count")
-                .help("dictionary bits count, in other words, dictionary size")
+                .help("dictionary bits count, i.e. dictionary size (used only for compression)")
                 .takes_value(true)
                 .short("b")
                 .long("bits")
                 .required(false)
                 .default_value("16"),
         )
+        .arg(
+            Arg::with_name("dictionary")
+                .help(
+                    "what to do with a full dictionary: reset it, lzt to replace \
+                     the least recently used strings, or clear to keep it until \
+                     the ratio drops (used only for compression)",
+                )
+                .takes_value(true)
+                .long("dictionary")
+                .possible_values(&["reset", "lzt", "clear"])
+                .required(false)
+                .default_value("reset"),
+        )
         .arg(
             Arg::with_name("password")
                 .help("password, enable aes encryption")
@@ -31,27 +83,475 @@ fn main() -> std::io::Result<()> {
                 .long("pass")
                 .required(false),
         )
+        .arg(
+            Arg::with_name("kdf_iterations")
+                .help("PBKDF2 iterations for the password, i.e. work factor (used only for compression)")
+                .takes_value(true)
+                .long("kdf-iterations")
+                .required(false)
+                .default_value("100000"),
+        )
+        .arg(
+            Arg::with_name("threads")
+                .help(
+                    "number of threads (1-256); above 1, compression splits the input into \
+                     independent 4 MiB blocks (no encryption), which extraction \
+                     then also decodes in parallel",
+                )
+                .takes_value(true)
+                .short("j")
+                .long("threads")
+                .required(false)
+                .default_value("1"),
+        )
+        .arg(
+            Arg::with_name("checksum")
+                .help(
+                    "store a checksum of the original data, verified on extraction \
+                     (used only for compression)",
+                )
+                .takes_value(true)
+                .long("checksum")
+                .possible_values(&["crc32", "xxhash64", "sha256"])
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("entropy")
+                .help(
+                    "entropy coding of the LZW codes: range makes the archive smaller \
+                     but compression and extraction slower (used only for compression)",
+                )
+                .takes_value(true)
+                .long("entropy")
+                .possible_values(&["none", "range"])
+                .required(false)
+                .default_value("none"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .help(
+                    "archive format for compression: lzw, or z for unix compress(1) .Z \
+                     (a single file or stdin, no encryption); extraction detects it",
+                )
+                .takes_value(true)
+                .long("format")
+                .possible_values(&["lzw", "z"])
+                .required(false)
+                .default_value("lzw"),
+        )
         .get_matches();
 
-    let source_file = matches.value_of("input_file").unwrap();
-    let result_file = matches.value_of("result_file").unwrap();
-    let bits_count: usize = matches.value_of("bits_count").unwrap().parse().unwrap();
-    match matches.value_of("mode").unwrap() {
+    let archive_file = matches.value_of("archive").unwrap();
+    let paths: Vec<&str> = matches
+        .values_of("paths")
+        .map_or_else(Vec::new, |values| values.collect());
+    let password = matches.value_of("password");
+    let threads = value_t!(matches, "threads", usize).unwrap_or_else(|e| e.exit());
+    let mode = matches.value_of("mode").unwrap();
+    if (mode == "l" || mode == "t") && !paths.is_empty() {
+        clap::Error::with_description(
+            "listing and testing take only the archive",
+            ErrorKind::TooManyValues,
+        )
+        .exit();
+    }
+    match mode {
         "a" => {
-            if let Some(pass) = matches.value_of("password") {
-                lzw::compress_aes(source_file, result_file, bits_count, pass)?;
+            let options = lzw::Options {
+                max_bits_count: value_t!(matches, "bits_count", usize).unwrap_or_else(|e| e.exit()),
+                dictionary_mode: match matches.value_of("dictionary") {
+                    Some("lzt") => lzw::DictionaryMode::Lzt,
+                    Some("clear") => lzw::DictionaryMode::Clear,
+                    _ => lzw::DictionaryMode::Reset,
+                },
+                password: password.map(String::from),
+                kdf_iterations: value_t!(matches, "kdf_iterations", u32)
+                    .unwrap_or_else(|e| e.exit()),
+                threads,
+                block_size: None,
+                checksum: match matches.value_of("checksum") {
+                    Some("crc32") => Some(lzw::Checksum::Crc32),
+                    Some("xxhash64") => Some(lzw::Checksum::XxHash64),
+                    Some("sha256") => Some(lzw::Checksum::Sha256),
+                    _ => None,
+                },
+                entropy: match matches.value_of("entropy") {
+                    Some("range") => Some(lzw::Entropy::Range),
+                    _ => None,
+                },
+            };
+            if matches.value_of("format") == Some("z") {
+                compress_z(archive_file, &paths, &options)?;
             } else {
-                lzw::compress(source_file, result_file, bits_count)?;
+                compress(archive_file, &paths, &options)?;
             }
         }
         "e" => {
-            if let Some(pass) = matches.value_of("password") {
-                lzw::decompress_aes(source_file, result_file, bits_count, pass)?;
-            } else {
-                lzw::decompress(source_file, result_file, bits_count)?;
-            }
+            let dest = match paths[..] {
+                [] => None,
+                [dest] => Some(dest),
+                _ => clap::Error::with_description(
+                    "only one destination can be given for extraction",
+                    ErrorKind::TooManyValues,
+                )
+                .exit(),
+            };
+            extract(archive_file, dest, password, threads)?;
         }
+        "l" => list(archive_file, password)?,
+        "t" => return test(archive_file, password, threads),
         _ => unreachable!(),
     }
+    Ok(true)
+}
+
+/// Сжимает файлы и каталоги в многофайловый архив, а стандартный ввод —
+/// в одиночный поток: у него нет ни имени, ни атрибутов
+fn compress(archive_file: &str, paths: &[&str], options: &lzw::Options) -> lzw::Result<()> {
+    let mut output = Output::new(archive_file);
+    if paths == [STDIO] {
+        let stdin = io::stdin();
+        lzw::compress_stream(stdin.lock(), &mut output, options)?;
+    } else if paths.contains(&STDIO) {
+        return Err(lzw::Error::InvalidOption(
+            "стандартный ввод нельзя сжимать вместе с файлами",
+        ));
+    } else {
+        let mut writer = ArchiveWriter::new(&mut output, options)?;
+        // Архив может оказаться внутри сжимаемого каталога
+        writer.exclude(archive_file)?;
+        for path in paths {
+            writer.add_path(path)?;
+        }
+        writer.finish()?;
+    }
+    Ok(output.finish()?)
+}
+/// Сжимает один файл или стандартный ввод в формат `.Z`
+fn compress_z(archive_file: &str, paths: &[&str], options: &lzw::Options) -> lzw::Result<()> {
+    if options.password.is_some() {
+        return Err(lzw::Error::InvalidOption(
+            "формат .Z не поддерживает шифрование",
+        ));
+    }
+    if options.dictionary_mode != lzw::DictionaryMode::Reset {
+        return Err(lzw::Error::InvalidOption(
+            "формат .Z не поддерживает выбор режима словаря",
+        ));
+    }
+    if options.threads > 1 {
+        return Err(lzw::Error::InvalidOption(
+            "формат .Z не поддерживает блочное сжатие",
+        ));
+    }
+    if options.checksum.is_some() {
+        return Err(lzw::Error::InvalidOption(
+            "формат .Z не поддерживает контрольную сумму",
+        ));
+    }
+    if options.entropy.is_some() {
+        return Err(lzw::Error::InvalidOption(
+            "формат .Z не поддерживает энтропийное кодирование",
+        ));
+    }
+    let mut reader: Box<dyn Read> = match paths {
+        [STDIO] => Box::new(io::stdin()),
+        [path] => Box::new(BufReader::new(File::open(path)?)),
+        _ => {
+            return Err(lzw::Error::InvalidOption(
+                "в формат .Z сжимается только один файл",
+            ));
+        }
+    };
+    // Слишком большое значение отвергнет сам кодировщик
+    let max_bits = u8::try_from(options.max_bits_count).unwrap_or(u8::MAX);
+    let mut output = Output::new(archive_file);
+    let mut encoder = ZEncoder::new(&mut output, max_bits)?;
+    io::copy(&mut reader, &mut encoder)?;
+    encoder.finish()?;
+    Ok(output.finish()?)
+}
+/// Распаковывает многофайловый архив в каталог, а одиночный поток — в файл
+fn extract(
+    archive_file: &str,
+    dest: Option<&str>,
+    password: Option<&str>,
+    threads: usize,
+) -> lzw::Result<()> {
+    let input = Input::open(archive_file)?;
+    if input.is_archive() {
+        let dest = dest.unwrap_or(".");
+        if dest == STDIO {
+            return Err(lzw::Error::InvalidOption(
+                "многофайловый архив распаковывается только в каталог",
+            ));
+        }
+        return open_archive(input, password, threads)?.extract_all(dest);
+    }
+    // Если указан каталог, имя файла берется из имени архива.
+    // Стандартный ввод по умолчанию распаковывается в стандартный вывод
+    let result_file = match dest {
+        None if archive_file == STDIO => STDIO.to_owned(),
+        Some(dest) if dest == STDIO || !Path::new(dest).is_dir() => dest.to_owned(),
+        _ if archive_file == STDIO => {
+            return Err(lzw::Error::InvalidOption("не указан файл результата"));
+        }
+        dest => {
+            let name = Path::new(archive_file).file_stem().unwrap_or_default();
+            let result_file = Path::new(dest.unwrap_or(".")).join(name);
+            result_file.to_string_lossy().into_owned()
+        }
+    };
+    let mut output = Output::new(&result_file);
+    decode_single(input, password, threads, &mut output)?;
+    Ok(output.finish()?)
+}
+fn open_archive(
+    input: Input,
+    password: Option<&str>,
+    threads: usize,
+) -> lzw::Result<ArchiveReader<Box<dyn ReadSeek>>> {
+    let mut reader = ArchiveReader::new(input.into_seekable()?)?;
+    if let Some(pass) = password {
+        reader.set_password(pass);
+    }
+    reader.set_threads(threads);
+    Ok(reader)
+}
+/// Распаковывает одиночный поток в writer. Поток читается за один проход,
+/// даже из стандартного ввода; зашифрованный выдается после проверки подлинности
+fn decode_single<W: Write>(
+    input: Input,
+    password: Option<&str>,
+    threads: usize,
+    writer: &mut W,
+) -> lzw::Result<()> {
+    match password {
+        Some(_) if input.is_z() => Err(lzw::Error::NotEncrypted),
+        None if input.is_z() => decode_z(input, writer),
+        Some(pass) => lzw::decompress_aes_stream(input.reader, writer, pass),
+        None => lzw::decompress_parallel(input.reader, writer, threads),
+    }
+}
+/// Распаковывает файл `.Z`: он не бывает зашифрован
+fn decode_z<W: Write>(input: Input, writer: &mut W) -> lzw::Result<()> {
+    io::copy(&mut ZDecoder::new(input.reader)?, writer)?;
+    Ok(())
+}
+
+/// Выводит содержимое архива
+fn list(archive_file: &str, password: Option<&str>) -> lzw::Result<()> {
+    println!(
+        "{:>12} {:>12} {:>7} {:>4} {:>3}  Name",
+        "Size", "Compressed", "Ratio", "Bits", "Enc"
+    );
+    let input = Input::open(archive_file)?;
+    if input.is_z() {
+        let mut reader = input.into_seekable()?;
+        let compressed_size = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+        let mut decoder = ZDecoder::new(reader)?;
+        let size = io::copy(&mut decoder, &mut io::sink())?;
+        let bits = decoder.max_bits().to_string();
+        print_row(size, compressed_size, &bits, "no", archive_file);
+        return Ok(());
+    }
+    if !input.is_archive() {
+        // Исходный размер одиночного потока известен только после распаковки
+        let mut reader = input.into_seekable()?;
+        let header = Header::read(&mut reader)?;
+        let compressed_size = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+        let mut sink = Counter(0);
+        match password {
+            Some(pass) => lzw::decompress_aes_stream(reader, &mut sink, pass)?,
+            None => lzw::decompress_stream(reader, &mut sink)?,
+        }
+        print_row(
+            sink.0,
+            compressed_size,
+            &header.max_bits_count.to_string(),
+            yes_no(header.is_encrypted()),
+            archive_file,
+        );
+        return Ok(());
+    }
+    let mut reader = open_archive(input, password, 1)?;
+    let (mut total_size, mut total_compressed) = (0, 0);
+    for index in 0..reader.entries().len() {
+        let entry = reader.entries()[index].clone();
+        let (bits, encrypted, name) = match entry.kind {
+            // У каталогов нет сжатого потока
+            EntryKind::Directory => ("-".to_owned(), "-", format!("{}/", entry.name)),
+            kind => {
+                let header = reader.header(index)?;
+                let name = match kind {
+                    EntryKind::Symlink => format!("{}@", entry.name),
+                    _ => entry.name.clone(),
+                };
+                let encrypted = yes_no(header.is_encrypted());
+                (header.max_bits_count.to_string(), encrypted, name)
+            }
+        };
+        print_row(entry.size, entry.compressed_size, &bits, encrypted, &name);
+        total_size += entry.size;
+        total_compressed += entry.compressed_size;
+    }
+    let total = format!("{} entries", reader.entries().len());
+    print_row(total_size, total_compressed, "", "", &total);
     Ok(())
-}
\ No newline at end of file
+}
+fn print_row(size: u64, compressed_size: u64, bits: &str, encrypted: &str, name: &str) {
+    // Доля сжатого размера от исходного
+    let ratio = if size == 0 {
+        "-".to_owned()
+    } else {
+        format!("{:.1}%", compressed_size as f64 * 100.0 / size as f64)
+    };
+    println!(
+        "{:>12} {:>12} {:>7} {:>4} {:>3}  {}",
+        size, compressed_size, ratio, bits, encrypted, name
+    );
+}
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+/// Проверяет целостность архива, распаковывая все записи без записи на диск.
+/// Возвращает false, если хотя бы одна запись повреждена
+fn test(archive_file: &str, password: Option<&str>, threads: usize) -> lzw::Result<bool> {
+    let input = Input::open(archive_file)?;
+    if !input.is_archive() {
+        let result = decode_single(input, password, threads, &mut io::sink());
+        return Ok(report(archive_file, result));
+    }
+    let mut reader = match open_archive(input, password, threads) {
+        Ok(reader) => reader,
+        Err(err) => return Ok(report(archive_file, Err(err))),
+    };
+    let mut intact = true;
+    for index in 0..reader.entries().len() {
+        let result = reader.extract(index, &mut io::sink());
+        intact &= report(&reader.entries()[index].name, result);
+    }
+    Ok(intact)
+}
+fn report(name: &str, result: lzw::Result<()>) -> bool {
+    match result {
+        Ok(()) => {
+            println!("OK      {}", name);
+            true
+        }
+        Err(err) => {
+            println!("FAILED  {}: {}", name, err);
+            false
+        }
+    }
+}
+
+/// Приемник, считающий записанные байты
+struct Counter(u64);
+impl Write for Counter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+trait ReadSeek: Read + Seek {}
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// Архив, открытый для чтения: файл или стандартный ввод
+struct Input {
+    path: String,
+    // Первые байты архива, по которым определяется его формат.
+    // Пустой файл .Z короче сигнатуры многофайлового архива
+    magic: Vec<u8>,
+    reader: Box<dyn Read>,
+}
+impl Input {
+    fn open(path: &str) -> lzw::Result<Input> {
+        let mut reader: Box<dyn Read> = if path == STDIO {
+            Box::new(io::stdin())
+        } else {
+            Box::new(BufReader::new(File::open(path)?))
+        };
+        let mut magic = Vec::with_capacity(archive::MAGIC.len());
+        reader
+            .by_ref()
+            .take(archive::MAGIC.len() as u64)
+            .read_to_end(&mut magic)?;
+        Ok(Input {
+            path: path.to_owned(),
+            // Считанные байты возвращаются в начало потока
+            reader: Box::new(Cursor::new(magic.clone()).chain(reader)),
+            magic,
+        })
+    }
+    /// Многофайловый архив или одиночный поток?
+    fn is_archive(&self) -> bool {
+        self.magic == archive::MAGIC
+    }
+    /// Файл `.Z` утилиты compress(1)?
+    fn is_z(&self) -> bool {
+        self.magic.starts_with(&lzc::MAGIC)
+    }
+    /// Многофайловому архиву и выводу содержимого нужен произвольный доступ,
+    /// поэтому стандартный ввод для них считывается в память
+    fn into_seekable(mut self) -> io::Result<Box<dyn ReadSeek>> {
+        if self.path == STDIO {
+            let mut data = Vec::new();
+            self.reader.read_to_end(&mut data)?;
+            return Ok(Box::new(Cursor::new(data)));
+        }
+        Ok(Box::new(BufReader::new(File::open(&self.path)?)))
+    }
+}
+
+/// Файл результата или стандартный вывод. Файл создается при первой записи,
+/// поэтому при неверном пароле или поврежденном заголовке он не появится
+struct Output {
+    path: String,
+    writer: Option<BufWriter<Box<dyn Write>>>,
+}
+impl Output {
+    fn new(path: &str) -> Self {
+        Output {
+            path: path.to_owned(),
+            writer: None,
+        }
+    }
+    fn writer(&mut self) -> io::Result<&mut BufWriter<Box<dyn Write>>> {
+        if self.writer.is_none() {
+            let writer: Box<dyn Write> = if self.path == STDIO {
+                Box::new(io::stdout())
+            } else {
+                Box::new(File::create(&self.path)?)
+            };
+            self.writer = Some(BufWriter::new(writer));
+        }
+        Ok(self.writer.as_mut().unwrap())
+    }
+    /// Сбрасывает буфер; пустой файл создается здесь
+    fn finish(mut self) -> io::Result<()> {
+        self.writer()?.flush()
+    }
+}
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer()?.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.writer {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
@@ -1,79 +1,343 @@
 // #![feature(async_await, await_macro, futures_api)]
 pub mod lzw {
-    use bitvec::{BigEndian, BitVec};
-    use indexmap::IndexSet;
+    use ring::{constant_time, hmac};
     use small_aes_rs::{AesCtx, Block, AES_BLOCKLEN};
     use std::fs::File;
     use std::io::{BufReader, BufWriter, Read, Write};
     type Index = u32;
 
+    pub mod archive;
+    pub mod bits;
+    pub mod block;
+    pub mod checksum;
+    mod codec;
+    pub mod entropy;
+    mod error;
+    pub mod gif;
+    pub mod header;
+    mod lru;
+    pub mod lzc;
+    pub mod shrink;
+    mod stream;
+    pub mod tiff;
+    use block::BlockEncoder;
+    pub use checksum::Checksum;
+    use checksum::{HashWriter, Hasher, Trailer};
+    use codec::{Prev, Table};
+    pub use entropy::Entropy;
+    use entropy::{CodeReader, CodeWriter};
+    pub use error::{Error, Result};
+    use header::{Header, FLAG_CLEAR, FLAG_LZT};
+    use lru::Lru;
+    pub use stream::{LzwDecoder, LzwEncoder, SeekableDecoder};
+
     // Модуль генерации, проверки ключа шифрования
     mod derive {
-        use ring::{digest, pbkdf2};
+        pub use super::header::Kdf;
+        use super::header::{KDF_PBKDF2_SHA256, SALT_LEN};
+        use super::{Error, Result};
+        use ring::{digest, hkdf, hmac, pbkdf2};
         use small_aes_rs::AES_KEYLEN;
         use std::num::NonZeroU32;
 
         const KEY_LEN: usize = AES_KEYLEN;
-        type CypherKey = [u8; KEY_LEN];
+        // Длина мастер-ключа и ключа HMAC совпадает с длиной вывода SHA256
+        const MASTER_KEY_LEN: usize = 32;
+        pub type CypherKey = [u8; KEY_LEN];
+        /// Длина проверочного значения ключа
+        pub const CHECK_LEN: usize = 4;
         // Алгоритм генерации псевдо-случайных чисел
-        static DIGEST_ALG: &'static digest::Algorithm = &digest::SHA256;
-        // Соль
-        const SALT: CypherKey = [
-            0xd6, 0x26, 0x98, 0xda, 0xf4, 0xdc, 0x50, 0x52, 0x24, 0xf2, 0x27, 0xd1, 0xfe, 0x39,
-            0x01, 0x8a,
-        ];
-        pub fn derive_key(secret: &str) -> CypherKey {
-            // Ключ
-            let mut key = [0u8; KEY_LEN];
-            // Количество итераций
-            let iterations = NonZeroU32::new(100_000).unwrap();
-            // Генерируем ключ
-            pbkdf2::derive(DIGEST_ALG, iterations, &SALT, secret.as_bytes(), &mut key);
-            key
+        static DIGEST_ALG: &digest::Algorithm = &digest::SHA256;
+        /// Ключи шифрования и аутентификации, полученные из пароля
+        pub struct Keys {
+            // Ключ AES
+            pub cypher: CypherKey,
+            // Ключ HMAC-SHA256
+            pub mac: hmac::SigningKey,
+            // Проверочное значение: усеченный HMAC мастер-ключа. Хранится в архиве
+            // и позволяет отличить неверный пароль от поврежденного архива
+            pub check: [u8; CHECK_LEN],
+        }
+        /// Параметры для нового архива: случайная соль и заданное количество итераций
+        pub fn new_kdf(iterations: u32) -> Result<Kdf> {
+            if iterations == 0 {
+                return Err(Error::InvalidOption(
+                    "количество итераций KDF должно быть больше нуля",
+                ));
+            }
+            let salt: [u8; SALT_LEN] = rand::random();
+            Ok(Kdf {
+                algorithm: KDF_PBKDF2_SHA256,
+                iterations,
+                salt,
+            })
+        }
+        pub fn derive_keys(secret: &str, kdf: &Kdf) -> Result<Keys> {
+            // Kdf может быть заполнен вручную, поэтому параметры проверяются повторно
+            let iterations = match (kdf.algorithm, NonZeroU32::new(kdf.iterations)) {
+                (KDF_PBKDF2_SHA256, Some(iterations)) => iterations,
+                _ => return Err(Error::BadHeader("неверные параметры получения ключа")),
+            };
+            // Мастер-ключ
+            let mut master = [0u8; MASTER_KEY_LEN];
+            // Генерируем мастер-ключ
+            pbkdf2::derive(
+                DIGEST_ALG,
+                iterations,
+                &kdf.salt,
+                secret.as_bytes(),
+                &mut master,
+            );
+            // Из мастер-ключа получаем независимые ключи для AES и HMAC
+            let prk = hmac::SigningKey::new(DIGEST_ALG, &master);
+            let mut cypher = [0u8; KEY_LEN];
+            hkdf::expand(&prk, b"lzw_arc aes", &mut cypher);
+            let mut mac = [0u8; MASTER_KEY_LEN];
+            hkdf::expand(&prk, b"lzw_arc hmac", &mut mac);
+            let mut check = [0u8; CHECK_LEN];
+            check.copy_from_slice(&hmac::sign(&prk, b"lzw_arc key check").as_ref()[..CHECK_LEN]);
+            Ok(Keys {
+                cypher,
+                mac: hmac::SigningKey::new(DIGEST_ALG, &mac),
+                check,
+            })
+        }
+        /// Ключи для заданного пароля. Получение ключа намеренно медленное,
+        /// поэтому ключи переиспользуются, пока не изменятся параметры KDF
+        pub struct KeyCache {
+            secret: String,
+            cached: Option<(Kdf, Keys)>,
+        }
+        impl KeyCache {
+            pub fn new(secret: &str) -> Self {
+                KeyCache {
+                    secret: secret.to_owned(),
+                    cached: None,
+                }
+            }
+            pub fn get(&mut self, kdf: &Kdf) -> Result<&Keys> {
+                let stale = match &self.cached {
+                    Some((cached, _)) => cached != kdf,
+                    None => true,
+                };
+                if stale {
+                    self.cached = Some((*kdf, derive_keys(&self.secret, kdf)?));
+                }
+                Ok(&self.cached.as_ref().unwrap().1)
+            }
+        }
+    }
+
+    /// Количество итераций PBKDF2 по умолчанию
+    pub const DEFAULT_KDF_ITERATIONS: u32 = 100_000;
+
+    /// Что делать с заполненным словарем
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum DictionaryMode {
+        /// Сбросить словарь и начать заново
+        #[default]
+        Reset,
+        /// Помещать новую строку на место той, которая дольше всех
+        /// не использовалась (LZT). Лучше сжимает большие однородные данные,
+        /// но работает медленнее
+        Lzt,
+        /// Использовать заполненный словарь, пока растет степень сжатия,
+        /// затем сбросить его кодом CLEAR, как `compress(1)`.
+        /// Индекс 256 занят кодом CLEAR
+        Clear,
+    }
+    impl DictionaryMode {
+        /// Режим, записанный в заголовке архива
+        fn of(header: &Header) -> DictionaryMode {
+            if header.flags & FLAG_LZT != 0 {
+                DictionaryMode::Lzt
+            } else if header.flags & FLAG_CLEAR != 0 {
+                DictionaryMode::Clear
+            } else {
+                DictionaryMode::Reset
+            }
+        }
+        /// Флаги заголовка для режима
+        fn flags(self) -> u8 {
+            match self {
+                DictionaryMode::Reset => 0,
+                DictionaryMode::Lzt => FLAG_LZT,
+                DictionaryMode::Clear => FLAG_CLEAR,
+            }
+        }
+        /// Первый индекс новых строк после сброса словаря
+        fn first(self) -> Index {
+            match self {
+                DictionaryMode::Clear => CLEAR + 1,
+                _ => 1 << 8,
+            }
+        }
+    }
+
+    /// Параметры сжатия
+    #[derive(Debug, Clone)]
+    pub struct Options {
+        /// Максимальное количество бит, т.е. размер словаря
+        pub max_bits_count: usize,
+        /// Что делать с заполненным словарем
+        pub dictionary_mode: DictionaryMode,
+        /// Пароль. Если задан, архив шифруется AES
+        pub password: Option<String>,
+        /// Количество итераций PBKDF2 при получении ключа из пароля.
+        /// Чем больше, тем дольше подбирать пароль (и открывать архив)
+        pub kdf_iterations: u32,
+        /// Количество потоков сжатия, не больше `block::MAX_THREADS`.
+        /// Больше одного — данные сжимаются независимыми блоками, см. [`block`]
+        pub threads: usize,
+        /// Размер блока. Если задан, данные сжимаются блоками и при одном
+        /// потоке; иначе при нескольких потоках используется `block::BLOCK_SIZE`.
+        /// Архив не зависит от количества потоков, только от размера блока
+        pub block_size: Option<usize>,
+        /// Алгоритм контрольной суммы исходных данных. Если задан, сумма
+        /// сохраняется в архиве и проверяется при распаковке
+        pub checksum: Option<Checksum>,
+        /// Энтропийный кодер кодов. Если задан, архив меньше, но сжатие
+        /// и распаковка медленнее, а в индексе блоков нет точек входа
+        /// внутри блоков
+        pub entropy: Option<Entropy>,
+    }
+    impl Default for Options {
+        fn default() -> Options {
+            Options {
+                max_bits_count: 16,
+                dictionary_mode: DictionaryMode::Reset,
+                password: None,
+                kdf_iterations: DEFAULT_KDF_ITERATIONS,
+                threads: 1,
+                block_size: None,
+                checksum: None,
+                entropy: None,
+            }
+        }
+    }
+    impl Options {
+        /// Заголовок архива с этими параметрами, без шифрования и блоков
+        fn header(&self) -> Result<Header> {
+            let max_bits_count = dictionary_bits(self.max_bits_count)?;
+            Ok(Header::new(max_bits_count, self.dictionary_mode.flags())
+                .with_checksum(self.checksum)
+                .with_entropy(self.entropy))
+        }
+        /// Размер блока, если данные сжимаются независимыми блоками
+        fn blocks(&self) -> Result<Option<usize>> {
+            block::check_threads(self.threads)?;
+            let block_size = match self.block_size {
+                Some(block_size) => block_size,
+                None if self.threads > 1 => block::BLOCK_SIZE,
+                None => return Ok(None),
+            };
+            if self.password.is_some() {
+                return Err(Error::InvalidOption(
+                    "блочное сжатие не поддерживает шифрование",
+                ));
+            }
+            Ok(Some(block_size))
+        }
+    }
+
+    /// Код сброса словаря в режиме `DictionaryMode::Clear`
+    const CLEAR: Index = 256;
+    /// Через сколько считанных байт снова проверяется степень сжатия
+    const CHECK_GAP: u64 = 10_000;
+
+    /// Степень сжатия в режиме `DictionaryMode::Clear`
+    #[derive(Default)]
+    struct Ratio {
+        // Количество считанных байт и записанных бит
+        in_count: u64,
+        bits_out: u64,
+        // Когда проверять степень сжатия в следующий раз
+        checkpoint: u64,
+        // Лучшая степень сжатия с последнего сброса словаря
+        best: u64,
+    }
+    impl Ratio {
+        /// Проверяет степень сжатия заполненного словаря. Возвращает true,
+        /// если она перестала расти и словарь пора сбросить
+        fn degraded(&mut self) -> bool {
+            if self.in_count < self.checkpoint {
+                return false;
+            }
+            self.checkpoint = self.in_count + CHECK_GAP;
+            // Отношение входа к выходу с фиксированной точкой (8 бит дробной части)
+            let ratio = (self.in_count << 11) / self.bits_out.max(1);
+            if ratio > self.best {
+                self.best = ratio;
+                false
+            } else {
+                self.best = 0;
+                true
+            }
         }
     }
 
     struct Compress {
-        // Словарь, для архивации
-        dictionary: IndexSet<Vec<u8>>,
+        // Словарь с поиском строк: (индекс префикса P, следующий байт C) -> индекс P + C.
+        // Индекс литерала равен значению байта
+        table: Table,
         // Текущее количество бит в максимальном значении словаря
         bits_count: u8,
         // Максимальное количество бит, т.е. размер словаря
         max_bits_count: u8,
-        // Предыдущая строка
-        prev: Vec<u8>,
-        // Буфер из бит, для добавления в результирующий поток
-        bit_buf: BitVec<BigEndian, u8>,
+        // Индекс предыдущей строки, None до первого байта
+        prev: Option<Index>,
+        // Строка P + C, отложенная при сбросе словаря
+        deferred: Option<Vec<u8>>,
+        // Что делать с заполненным словарем
+        mode: DictionaryMode,
+        // Учет обращений к строкам, только в режиме LZT
+        lru: Option<Lru>,
+        // Степень сжатия, только в режиме CLEAR
+        ratio: Option<Ratio>,
+        // Количество считанных байт и записанных бит
+        in_count: u64,
+        bits_out: u64,
+        // Точки входа после сброса словаря, если их нужно записать в индекс
+        points: Option<Vec<block::Point>>,
+        // Буфер из бит (или энтропийный кодер), для добавления в результирующий поток
+        bits: CodeWriter,
     }
     struct Decompress {
-        // Словарь, для архивации
-        dictionary: Vec<Vec<u8>>,
+        // Словарь: индекс префикса, последний байт и длина каждой строки.
+        // Строка восстанавливается проходом по префиксам с конца
+        table: Table,
         bits_count: usize,
         // Максимальное количество бит, т.е. размер словаря
         max_bits_count: u8,
-        // Текущий считанный индекс кодового слова
-        index: usize,
-        // Прошлое кодовое слово
-        string: Vec<u8>,
-        // Буфер из бит, для добавления в результирующий поток
-        bit_buf: BitVec<BigEndian, u8>,
+        // Индекс прошлого кодового слова, None в начале потока, после сброса и CLEAR
+        prev: Option<Index>,
+        // Прошлое кодовое слово из сброшенного словаря. Строка 256 нового
+        // словаря продолжает его, поэтому хранится в словаре целиком
+        carried: Vec<u8>,
+        // Что делать с заполненным словарем
+        mode: DictionaryMode,
+        // Учет обращений к строкам, только в режиме LZT
+        lru: Option<Lru>,
+        // Буфер из бит (или энтропийный декодер), считанных из потока
+        bits: CodeReader,
     }
     impl Default for Compress {
         fn default() -> Compress {
             // Инициализируем словарь из всех значений, которые можно хранить
-            // в одном байте (0..255)
-            // Выделяем памяти в словаре под 65536 значений (для размера словаря по-умолчанию в 16 бит)
-            let mut dictionary: IndexSet<Vec<u8>> =
-                IndexSet::with_capacity(u16::max_value() as usize);
-            for ch in u8::min_value()..=u8::max_value() {
-                dictionary.insert(vec![ch]);
-            }
+            // в одном байте (0..255). Память под остальные строки выделяет `sized`
             Compress {
-                dictionary,
+                table: Table::new(1 << 8, table_size(16), true),
                 bits_count: 8,
                 max_bits_count: 16,
-                prev: Vec::with_capacity(64),
-                bit_buf: BitVec::with_capacity(32),
+                prev: None,
+                deferred: None,
+                mode: DictionaryMode::Reset,
+                lru: None,
+                ratio: None,
+                in_count: 0,
+                bits_out: 0,
+                points: None,
+                bits: CodeWriter::new(None),
             }
         }
     }
@@ -82,279 +346,648 @@ pub mod lzw {
             // Инициализируем словарь из всех значений, которые можно хранить
             // в одном байте (0..255)
             // Выделяем памяти в словаре под 65536 значений (для размера словаря по-умолчанию в 16 бит)
-            let mut dictionary: Vec<Vec<u8>> = Vec::with_capacity(u16::max_value() as usize);
-            for ch in u8::min_value()..=u8::max_value() {
-                dictionary.push(vec![ch]);
-            }
+            let mut table = Table::new(1 << 8, table_size(16), false);
+            table.reserve(u16::MAX as usize);
             Decompress {
-                dictionary,
+                table,
                 bits_count: 8,
                 max_bits_count: 16,
-                index: 0,
-                string: Vec::new(),
-                bit_buf: BitVec::with_capacity(64),
+                prev: None,
+                carried: Vec::new(),
+                mode: DictionaryMode::Reset,
+                lru: None,
+                bits: CodeReader::new(None),
             }
         }
     }
     impl Compress {
-        fn new(max_bits_count: u8) -> Self {
-            if max_bits_count > 32 || max_bits_count < 9 {
-                panic!("Недопустимый размер словаря! Разрешенный: 9 <= n <= 32");
-            }
-            Self {
+        /// Создает кодировщик с параметрами из заголовка архива
+        fn new(header: &Header) -> Result<Self> {
+            Compress::sized(header, usize::MAX)
+        }
+        /// Создает кодировщик для входных данных длиной не больше input_len.
+        /// Каждый байт добавляет в словарь не больше одной строки, поэтому
+        /// память выделяется по меньшему из размеров словаря и входа
+        fn sized(header: &Header, input_len: usize) -> Result<Self> {
+            let max_bits_count = dictionary_bits(header.max_bits_count as usize)?;
+            // Не больше 65536 строк заранее (для размера словаря по-умолчанию в 16 бит)
+            let capacity = (1usize << max_bits_count.min(16)).min(input_len);
+            let mode = DictionaryMode::of(header);
+            let mut compress = Self {
                 max_bits_count,
+                mode,
+                lru: new_lru(mode),
+                bits: CodeWriter::new(header.entropy),
+                ratio: match mode {
+                    DictionaryMode::Clear => Some(Ratio::default()),
+                    _ => None,
+                },
+                table: Table::new(1 << 8, table_size(max_bits_count), true),
                 ..Default::default()
-            }
+            };
+            compress.table.reserve(capacity);
+            // Начальный словарь зависит от режима
+            compress.reset_dictionary();
+            Ok(compress)
         }
+        /// Сжимает данные из reader. Состояние словаря сохраняется между вызовами,
+        /// поэтому поток можно подавать частями. Буферизацию reader и writer
+        /// обеспечивает вызывающая сторона
         fn compress<R: Read, W: Write>(
             &mut self,
-            reader: R,
+            mut reader: R,
             writer: &mut W,
         ) -> std::io::Result<()> {
-            // Создаем буфер для reader, что ускорит чтение
-            let mut reader = BufReader::new(reader);
-            // Создаем буфер для writer, что ускорит запись
-            let mut writer = BufWriter::new(writer);
             // Буфер для считываемого байта
             let mut buf = [0u8; 1];
             // Основной цикл алгоритма. Считываем по одному байту, пока не закончится файл
             while reader.read(&mut buf)? != 0 {
                 // Текущий символ
                 let current: u8 = buf[0];
-                self.prev.push(current);
+                self.in_count += 1;
+                if let Some(ratio) = self.ratio.as_mut() {
+                    ratio.in_count += 1;
+                }
+                let prefix = match self.prev {
+                    Some(prefix) => prefix,
+                    None => {
+                        self.prev = Some(Index::from(current));
+                        continue;
+                    }
+                };
                 // Набор байт уже присутствует в словаре?
-                if !self.dictionary.contains(&self.prev) {
-                    // Добавляем P в буфер
-                    self.append_to_buf(self.prev[0..self.prev.len() - 1].to_vec());
+                if let Some(index) = self.table.find(prefix, current) {
+                    self.prev = Some(index);
+                    continue;
+                }
+                // Добавляем P в буфер
+                self.push_code(prefix, writer)?;
+                // Строка, отложенная при сбросе, попадает в словарь
+                // только после первого кодового слова нового словаря
+                if let Some(string) = self.deferred.take() {
+                    self.table.insert_detached(string);
+                }
+                if self.mode == DictionaryMode::Lzt {
+                    self.insert_lru(prefix, current);
+                } else if self.mode == DictionaryMode::Clear {
+                    self.insert_or_clear(prefix, current, writer)?;
+                // Сбрасываем словарь, если достигли максимального количества бит
+                } else if self.table.len() as usize + 1 == 1 << self.max_bits_count as usize {
+                    // Следующее кодовое слово записывается 8 битами, поэтому
+                    // оно не должно ссылаться на P + C с индексом 256
+                    let mut string = self.table.string(prefix);
+                    self.mark_point(&string);
+                    string.push(current);
+                    self.deferred = Some(string);
+                    self.reset_dictionary();
+                } else {
                     // Меняем номер последнего ключа в словаре
-                    self.add_element_count();
-                    // P + C в словарь
-                    self.dictionary.insert(self.prev.clone());
-                    // P = C
-                    self.prev.clear();
-                    self.prev.push(current);
-                    while let Some(byte) = pop_byte(&mut self.bit_buf) {
-                        writer.write_all(&[byte])?;
+                    self.bits_count = get_bits_count(self.table.len()) as u8;
+                    if self.table.find(prefix, current).is_some() {
+                        // P + C совпала с отложенной строкой. Декодер добавит ее
+                        // повторно, поэтому занимаем индекс строкой,
+                        // которая никогда не будет найдена
+                        self.table.skip();
+                    } else {
+                        self.table.insert(prefix, current);
                     }
                 }
+                // P = C
+                self.prev = Some(Index::from(current));
             }
             Ok(())
         }
         /// Добавляет оставшиеся в буфере байты в заданный поток
         fn last_bytes<W: Write>(&mut self, writer: &mut W) -> std::io::Result<()> {
-            // Добавляем в буфер оставшиеся байты
-            self.append_to_buf(self.prev.to_vec());
-            let last_bytes: Vec<u8> = self.bit_buf.as_slice().to_vec();
+            // Добавляем в буфер оставшиеся байты (если входной поток не был пуст)
+            if let Some(prev) = self.prev {
+                self.push_code(prev, writer)?;
+            }
             // Добавляем в файл последние байты, дополняя их нулями
-            writer.write_all(&last_bytes)?;
-            Ok(())
+            self.bits.finish(writer)
         }
-        /// Добавляем в буфер кодовое значение из словаря, для дальнейшего добавления в файл
-        fn append_to_buf(&mut self, value: Vec<u8>) {
-            let (index, _) = self.dictionary.get_full(&value).expect(
-                "Ошибка при получении значения из словаря",
-            );
-            self.bit_buf
-                .append(&mut from_index(index as Index, self.bits_count));
-        }
-        // Увеличиваем счетчик словаря
-        fn add_element_count(&mut self) -> bool {
-            let bits_count = get_bits_count(self.dictionary.len() as Index) as u8;
-            // Сбрасываем словарь, если достигли максимального количества бит
-            if self.dictionary.len() + 1 == (1 << self.max_bits_count) as usize {
+        /// Добавляет индекс в буфер текущим количеством бит.
+        /// Набравшиеся полные байты записываются в writer
+        fn push_code<W: Write>(&mut self, index: Index, writer: &mut W) -> std::io::Result<()> {
+            if let Some(ratio) = self.ratio.as_mut() {
+                ratio.bits_out += u64::from(self.bits_count);
+            }
+            self.bits_out += u64::from(self.bits_count);
+            self.bits.write_code(writer, index, self.bits_count)
+        }
+        /// Запоминает точку входа после сброса словаря: с нее начинается
+        /// строка из текущего байта. carried — последнее кодовое слово
+        /// сброшенного словаря, которое продолжит первая строка нового
+        fn mark_point(&mut self, carried: &[u8]) {
+            let (offset, bit_offset) = (self.in_count - 1, self.bits_out);
+            if let Some(points) = self.points.as_mut() {
+                points.push(block::Point {
+                    offset,
+                    bit_offset,
+                    carried: carried.to_vec(),
+                });
+            }
+        }
+        /// Добавляет P + C в словарь в режиме CLEAR. Заполненный словарь
+        /// используется без изменений, пока растет степень сжатия, затем
+        /// в поток записывается код CLEAR и словарь сбрасывается
+        fn insert_or_clear<W: Write>(
+            &mut self,
+            prefix: Index,
+            byte: u8,
+            writer: &mut W,
+        ) -> std::io::Result<()> {
+            let len = self.table.len();
+            if len as usize + 1 < 1 << self.max_bits_count as usize {
+                self.bits_count = get_bits_count(len) as u8;
+                self.table.insert(prefix, byte);
+            } else if self
+                .ratio
+                .as_mut()
+                .expect("Словарь не в режиме CLEAR")
+                .degraded()
+            {
+                self.push_code(CLEAR, writer)?;
                 self.reset_dictionary();
-                true
-            } else {
-                self.bits_count = bits_count;
-                false
+                self.mark_point(&[]);
             }
+            Ok(())
         }
-        fn reset_dictionary(&mut self) {
-            // Инициализируем словарь из всех значений, которые можно хранить
-            // в одном байте (0..255)
-            self.dictionary.clear();
-            for ch in u8::min_value()..=u8::max_value() {
-                self.dictionary.insert(vec![ch]);
+        /// Добавляет P + C в словарь в режиме LZT. Заполненный словарь
+        /// не сбрасывается: P + C занимает индекс строки, которая дольше всех
+        /// не использовалась. prefix — только что записанный индекс P
+        fn insert_lru(&mut self, prefix: Index, byte: u8) {
+            let lru = self.lru.as_mut().expect("Словарь не в режиме LZT");
+            lru.touch(prefix);
+            let len = self.table.len();
+            if len as usize + 1 < 1 << self.max_bits_count as usize {
+                self.bits_count = get_bits_count(len) as u8;
+                lru.insert(len, prefix);
+                self.table.insert(prefix, byte);
+                return;
             }
-            self.bits_count = 8;
+            let victim = match lru.victim(prefix) {
+                Some(victim) => victim,
+                None => return,
+            };
+            lru.evict(victim);
+            lru.insert(victim, prefix);
+            // Вытесняемая строка — лист, поэтому ссылок на ее индекс в словаре нет
+            self.table.replace(victim, prefix, byte);
+        }
+        fn reset_dictionary(&mut self) {
+            // В словаре остаются только значения, которые можно хранить
+            // в одном байте (0..255), и код CLEAR
+            self.table.reset(self.mode.first());
+            // Следующее кодовое слово — литерал
+            self.bits_count = get_bits_count(self.table.len() - 1) as u8;
         }
     }
     impl Decompress {
-        fn new(max_bits_count: u8) -> Self {
-            if max_bits_count > 32 || max_bits_count < 9 {
-                panic!("Недопустимый размер словаря! Разрешенный: 9 <= n <= 32");
-            }
-            Self {
+        /// Создает декодировщик с параметрами из заголовка архива
+        fn new(header: &Header) -> Result<Self> {
+            let max_bits_count = dictionary_bits(header.max_bits_count as usize)?;
+            let mode = DictionaryMode::of(header);
+            let mut decompress = Self {
                 max_bits_count,
+                mode,
+                lru: new_lru(mode),
+                bits: CodeReader::new(header.entropy),
                 ..Default::default()
-            }
+            };
+            // Начальный словарь зависит от режима
+            decompress.reset_dictionary();
+            Ok(decompress)
         }
-        fn decompress<R: Read, W: Write>(
-            &mut self,
-            reader: R,
-            writer: &mut W,
-        ) -> std::io::Result<()> {
-            // Создаем буфер для reader, что ускорит чтение
-            let mut reader = BufReader::new(reader);
-            // Создаем буфер для writer, что ускорит запись
-            let mut writer = BufWriter::new(writer);
-            // Буфер для считываемого байта
-            let mut buf = [0u8; 1];
+        /// Распаковывает данные из reader до конца потока. Неполное кодовое слово
+        /// в конце остается в буфере и будет дополнено при следующем вызове
+        fn decompress<R: Read, W: Write>(&mut self, mut reader: R, writer: &mut W) -> Result<()> {
+            let mut code = Vec::new();
             // Основной цикл алгоритма
-            loop {
-                // Считываем из буфера по байту, пока не достигнем нужного,
-                // для извлечения индекса, количества бит
-                while self.bit_buf.len() < self.bits_count {
-                    if reader.read(&mut buf)? != buf.len() {
-                        // Если встретили конец файла, завершаем работу алгоритма
-                        return Ok(());
-                    }
-                    // Добавляем байт в буфер
-                    self.bit_buf.append(&mut from_index(u32::from(buf[0]), 8));
-                }
-                // Извлекаем индекс
-                let index_tmp: Index = pop_first_bits(&mut self.bit_buf, self.bits_count as u8).expect(
-                    "Ошибка в извлечении индекса из битового буфера"
-                );
-                // Меняем тип к usize, чтобы индексировать вектор
-                self.index = index_tmp as usize;
-                // Если индекс больше размера массива, значит файл некорректен
-                if self.index > self.dictionary.len() {
-                    panic!("Неверный зашифрованный код");
-                // Если индекс равен размеру словаря, то кодового слова нет, добавим в словарь
-                } else if self.index == self.dictionary.len() {
-                    self.string.push(self.string[0]);
-                // Если элемент с заданным индексом есть в словаре
-                } else if !self.string.is_empty() {
-                    self.string.push(self.dictionary[self.index][0]);
-                }
-                // Добавление в словарь
-                if !self.string.is_empty() {
-                    self.dictionary.push(self.string.clone());
-                }
-                let code = self.dictionary.get(self.index).expect(
-                    "Ошибка в извлечении кодового слова из словаря"
-                );
+            while self.decode_next(&mut reader, &mut code)? {
                 // Записываем в файл
-                writer.write_all(&code[..])?;
-                self.string = code.to_vec();
-                // Сбрасываем словарь, если наполнили его
-                if self.dictionary.len() + 1 == 1 << self.max_bits_count as usize {
+                writer.write_all(&code)?;
+                code.clear();
+            }
+            Ok(())
+        }
+        /// Декодирует очередное кодовое слово и дописывает его в out.
+        /// Возвращает false, если поток закончился раньше, чем набралось кодовое слово
+        fn decode_next<R: Read>(&mut self, reader: &mut R, out: &mut Vec<u8>) -> Result<bool> {
+            let index = loop {
+                // Извлекаем индекс
+                let index: Index = match self.bits.read_code(reader, self.bits_count as u8)? {
+                    Some(index) => index,
+                    // Если встретили конец файла, завершаем работу алгоритма
+                    None => return Ok(false),
+                };
+                // Код CLEAR сбрасывает словарь, за ним следует литерал
+                if self.mode == DictionaryMode::Clear && index == CLEAR {
                     self.reset_dictionary();
-                    // Для первого считываемого байта, возьмем количество бит от размера словаря минус 1
-                    self.bits_count = get_bits_count((self.dictionary.len() - 1) as Index);
+                    self.prev = None;
                 } else {
-                    // Количество бит для считывания следующего индекса
-                    self.bits_count = get_bits_count(self.dictionary.len() as Index);
+                    break index;
+                }
+            };
+            let len = self.table.len() as usize;
+            let full = len + 1 == 1 << self.max_bits_count as usize;
+            // Индекс, под которым в словарь попадет прошлое кодовое слово + C.
+            // В заполненном словаре LZT это индекс вытесняемой строки,
+            // а заполненный словарь CLEAR не меняется
+            let target = match (&self.lru, self.prev) {
+                _ if self.prev.is_none() && self.carried.is_empty() => None,
+                (Some(lru), Some(prev)) if full => lru.victim(prev),
+                _ if full && self.mode == DictionaryMode::Clear => None,
+                _ => Some(len as Index),
+            };
+            if let (Some(lru), Some(target)) = (self.lru.as_mut(), target) {
+                if (target as usize) < len {
+                    lru.evict(target);
                 }
+                lru.insert(target, self.prev.unwrap_or(0));
             }
+            // Прошлое кодовое слово может остаться от сброшенного словаря
+            let prev = match self.prev {
+                Some(prev) => Prev::Code(prev),
+                None => Prev::Carried(&self.carried),
+            };
+            let start = out.len();
+            // Индекс вне словаря означает, что файл некорректен
+            // (или ссылается на еще не созданное слово в самом начале потока)
+            self.table
+                .decode(index, target.map(|target| (target, prev)), out)?;
+            if let Some(lru) = self.lru.as_mut() {
+                lru.touch(index);
+            }
+            self.prev = Some(index);
+            self.carried.clear();
+            // Сбрасываем словарь, если наполнили его. В остальных режимах
+            // заполненный словарь не сбрасывается
+            if self.mode == DictionaryMode::Reset
+                && self.table.len() as usize + 1 == 1 << self.max_bits_count as usize
+            {
+                // Первая строка нового словаря продолжит текущее кодовое слово
+                self.carried.extend_from_slice(&out[start..]);
+                self.prev = None;
+                self.reset_dictionary();
+            } else {
+                // Количество бит для считывания следующего индекса
+                self.bits_count = get_bits_count(self.table.len());
+            }
+            Ok(true)
         }
         fn reset_dictionary(&mut self) {
-            // Инициализируем словарь из всех значений, которые можно хранить
-            // в одном байте (0..255)
-            self.dictionary.clear();
-            for ch in u8::min_value()..=u8::max_value() {
-                self.dictionary.push(vec![ch]);
-            }
+            // В словаре остаются только значения, которые можно хранить
+            // в одном байте (0..255), и код CLEAR
+            self.table.reset(self.mode.first());
+            // Для первого считываемого байта, возьмем количество бит от размера словаря минус 1
+            self.bits_count = get_bits_count(self.table.len() - 1);
+        }
+    }
+    /// Учет обращений к строкам для заданного режима словаря
+    fn new_lru(mode: DictionaryMode) -> Option<Lru> {
+        match mode {
+            DictionaryMode::Lzt => Some(Lru::new()),
+            _ => None,
         }
     }
+    /// Количество строк в словаре с заданным количеством бит. Последний
+    /// индекс не используется: словарь сбрасывается или заполнен раньше
+    fn table_size(max_bits_count: u8) -> Index {
+        ((1u64 << max_bits_count) - 1) as Index
+    }
     /// Получает количество бит числа, без лидирующих нулей
     fn get_bits_count(length: Index) -> usize {
         let bits_in_type = Index::from(0u8).count_zeros();
         (bits_in_type - length.leading_zeros()) as usize
     }
-    /// Преобразует value в BitVec длиной bits
-    fn from_index(value: Index, bits: u8) -> BitVec<BigEndian, u8> {
-        let mut bv: BitVec<BigEndian, u8> = BitVec::with_capacity(bits as usize);
-        for i in (0..bits).rev() {
-            // Добавляем i-ый бит в bv
-            bv.push(((1 << i) & value) != 0);
-        }
-        bv
-    }
-    /// Получает из BitVec байты (u8) для записи в файл
-    fn pop_byte(bv: &mut BitVec<BigEndian, u8>) -> Option<u8> {
-        if let Some(byte) = pop_first_bits(bv, 8) {
-            return Some(byte as u8);
-        }
-        None
-    }
-    /// Получает из BitVec число, состоящее из первых bits бит
-    fn pop_first_bits(bv: &mut BitVec<BigEndian, u8>, bits: u8) -> Option<Index> {
-        let bits = bits as usize;
-        // Если есть что получить из буфера
-        if bv.len() >= bits {
-            let bv2 = bv.split_off(bits);
-            let mut index: Index = 0;
-            // Преобразовываем BitVec в Index
-            for (i, j) in (0..bv.len()).rev().enumerate() {
-                index |= (bv[j] as Index) << i;
+
+    /// Проверяет допустимость размера словаря
+    fn dictionary_bits(max_bits_count: usize) -> Result<u8> {
+        if !(9..=32).contains(&max_bits_count) {
+            return Err(Error::UnsupportedBits(max_bits_count));
+        }
+        Ok(max_bits_count as u8)
+    }
+    /// Считывает заголовок архива и проверяет режим шифрования
+    fn read_header<R: Read>(reader: &mut R, encrypted: bool) -> Result<Header> {
+        let header = Header::read(reader)?;
+        match (header.is_encrypted(), encrypted) {
+            (true, false) => Err(Error::PasswordRequired),
+            (false, true) => Err(Error::NotEncrypted),
+            _ => Ok(header),
+        }
+    }
+    /// Считывает из потока полный блок, либо все, что осталось до конца потока
+    fn read_block<R: Read>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut total = 0;
+        while total < buf.len() {
+            match reader.read(&mut buf[total..])? {
+                0 => break,
+                bytes_read => total += bytes_read,
             }
-            *bv = bv2;
-            return Some(index);
         }
-        None
+        Ok(total)
+    }
+
+    /// Дополняет данные до целого числа блоков AES по PKCS#7: добавляется
+    /// от 1 до AES_BLOCKLEN байт, каждый из которых равен их количеству
+    fn pad_block(buf: &mut Vec<u8>) {
+        let pad = AES_BLOCKLEN - buf.len() % AES_BLOCKLEN;
+        buf.resize(buf.len() + pad, pad as u8);
+    }
+    /// Удаляет дополнение PKCS#7 из последнего расшифрованного блока
+    fn unpad_block(buf: &mut Vec<u8>) -> Result<()> {
+        let pad = *buf.last().ok_or(Error::InvalidPadding)? as usize;
+        if pad == 0 || pad > AES_BLOCKLEN || pad > buf.len() {
+            return Err(Error::InvalidPadding);
+        }
+        if buf[buf.len() - pad..]
+            .iter()
+            .any(|&byte| byte as usize != pad)
+        {
+            return Err(Error::InvalidPadding);
+        }
+        buf.truncate(buf.len() - pad);
+        Ok(())
     }
 
     /// Запускает компрессию файла
-    pub fn compress(
-        source_file: &str,
-        result_file: &str,
-        max_bits_count: usize,
-    ) -> std::io::Result<()> {
-        let mut lzw_struct = Compress::new(max_bits_count as u8);
-        let reader = File::open(source_file)?;
-        let mut writer = File::create(result_file)?;
+    pub fn compress(source_file: &str, result_file: &str, max_bits_count: usize) -> Result<()> {
+        let options = Options {
+            max_bits_count,
+            ..Default::default()
+        };
+        compress_with(source_file, result_file, &options)
+    }
+    /// Запускает компрессию файла с заданными параметрами
+    pub fn compress_with(source_file: &str, result_file: &str, options: &Options) -> Result<()> {
+        let reader = BufReader::new(File::open(source_file)?);
+        let mut writer = BufWriter::new(File::create(result_file)?);
+        compress_stream(reader, &mut writer, options)?;
+        writer.flush()?;
+        Ok(())
+    }
+    /// Запускает декомпрессию файла. Размер словаря берется из заголовка архива
+    pub fn decompress(source_file: &str, result_file: &str) -> Result<()> {
+        // Заголовок проверяется до того, как будет создан файл результата
+        let mut decoder = LzwDecoder::new(File::open(source_file)?)?;
+        let mut writer = BufWriter::new(File::create(result_file)?);
+        std::io::copy(&mut decoder, &mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+    /// Сжимает данные из reader в writer с заданными параметрами, например
+    /// из стандартного ввода в стандартный вывод. Буферизацию reader
+    /// обеспечивает вызывающая сторона
+    pub fn compress_stream<R: Read, W: Write>(
+        mut reader: R,
+        writer: &mut W,
+        options: &Options,
+    ) -> Result<()> {
+        let header = options.header()?;
+        if let Some(block_size) = options.blocks()? {
+            let mut encoder = BlockEncoder::new(writer, header, block_size, options.threads)?;
+            std::io::copy(&mut reader, &mut encoder)?;
+            encoder.finish()?;
+            return Ok(());
+        }
+        if let Some(secret) = &options.password {
+            let kdf = derive::new_kdf(options.kdf_iterations)?;
+            let keys = derive::derive_keys(secret, &kdf)?;
+            return compress_aes_stream(reader, writer, header, &kdf, &keys);
+        }
+        // Кодировщик сразу записывает заголовок архива
+        let mut encoder = LzwEncoder::with_header(writer, header)?;
         // Сжимаем
-        lzw_struct.compress(reader, &mut writer)?;
-        // Обязательно вызываем last_bytes, переносим внутренний буфер в поток
-        lzw_struct.last_bytes(&mut writer)?;
+        std::io::copy(&mut reader, &mut encoder)?;
+        // Обязательно вызываем finish, переносим внутренний буфер в поток
+        encoder.finish()?;
         Ok(())
     }
-    /// Запускает декомпрессию файла
-    pub fn decompress(
-        source_file: &str,
-        result_file: &str,
+    /// Распаковывает архив из reader в writer. Размер словаря берется из заголовка архива
+    pub fn decompress_stream<R: Read, W: Write>(reader: R, writer: &mut W) -> Result<()> {
+        let mut decoder = LzwDecoder::new(reader)?;
+        std::io::copy(&mut decoder, writer)?;
+        Ok(())
+    }
+    /// Распаковывает архив из reader в writer, распаковывая блоки блочного
+    /// архива в threads потоков. Обычный архив распаковывается в одном потоке
+    pub fn decompress_parallel<R: Read, W: Write>(
+        reader: R,
+        writer: &mut W,
+        threads: usize,
+    ) -> Result<()> {
+        let mut reader = BufReader::new(reader);
+        let header = read_header(&mut reader, false)?;
+        if header.has_blocks() {
+            return block::decompress_blocks(reader, writer, &header, threads);
+        }
+        decompress_codes(reader, writer, &header)
+    }
+    /// Распаковывает коды обычного архива, следующие за заголовком,
+    /// и сверяет контрольную сумму, если она хранится в архиве
+    fn decompress_codes<R: Read, W: Write>(
+        reader: R,
+        writer: &mut W,
+        header: &Header,
+    ) -> Result<()> {
+        let mut lzw_struct = Decompress::new(header)?;
+        let checksum = match header.checksum {
+            Some(checksum) => checksum,
+            None => return lzw_struct.decompress(reader, writer),
+        };
+        // Контрольная сумма занимает последние байты потока
+        let mut reader = BufReader::new(Trailer::new(reader, checksum.output_len()));
+        let mut writer = HashWriter {
+            writer,
+            hasher: Some(Hasher::new(checksum)),
+        };
+        lzw_struct.decompress(&mut reader, &mut writer)?;
+        let hasher = writer.hasher.expect("Контрольная сумма не вычислялась");
+        hasher.verify(reader.get_ref().trailer()?)
+    }
+    /// Сжимает срез в памяти
+    pub fn compress_bytes(data: &[u8], max_bits_count: usize) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        compress_bytes_into(data, max_bits_count, &mut out)?;
+        Ok(out)
+    }
+    /// Сжимает срез в памяти, дописывая архив в конец out.
+    /// Позволяет переиспользовать выделенную память между вызовами
+    pub fn compress_bytes_into(
+        data: &[u8],
         max_bits_count: usize,
-    ) -> std::io::Result<()> {
-        let mut lzw_struct = Decompress::new(max_bits_count as u8);
-        let reader = File::open(source_file)?;
-        let mut writer = File::create(result_file)?;
-        lzw_struct.decompress(reader, &mut writer)?;
+        out: &mut Vec<u8>,
+    ) -> Result<()> {
+        let options = Options {
+            max_bits_count,
+            ..Default::default()
+        };
+        compress_bytes_with(data, &options, out)
+    }
+    /// Сжимает срез в памяти с заданными параметрами, дописывая архив в конец out
+    pub fn compress_bytes_with(data: &[u8], options: &Options, out: &mut Vec<u8>) -> Result<()> {
+        if options.password.is_some() || options.blocks()?.is_some() {
+            return compress_stream(data, out, options);
+        }
+        // Вектору не нужна буферизация, а словарю — память больше, чем на весь вход
+        let header = options.header()?;
+        let mut lzw_struct = Compress::sized(&header, data.len())?;
+        header.write(out)?;
+        lzw_struct.compress(data, out)?;
+        lzw_struct.last_bytes(out)?;
+        if let Some(checksum) = header.checksum {
+            let mut hasher = Hasher::new(checksum);
+            hasher.update(data);
+            out.extend_from_slice(&hasher.finish());
+        }
         Ok(())
     }
+    /// Распаковывает архив, находящийся в памяти
+    pub fn decompress_bytes(data: &[u8]) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        decompress_bytes_into(data, &mut out)?;
+        Ok(out)
+    }
+    /// Распаковывает архив, находящийся в памяти, дописывая результат в конец out
+    pub fn decompress_bytes_into(mut data: &[u8], out: &mut Vec<u8>) -> Result<()> {
+        let header = read_header(&mut data, false)?;
+        if header.has_blocks() {
+            return block::decompress_blocks(data, out, &header, 1);
+        }
+        decompress_codes(data, out, &header)
+    }
+
     /* Компрессия и декомпрессия с AES шифрованием */
+    // Архив: заголовок, IV, проверочное значение ключа, шифротекст (AES-CBC, PKCS#7)
+    // и HMAC-SHA256 от всего предшествующего содержимого (encrypt-then-MAC)
+
+    /// Длина кода аутентификации в конце зашифрованного архива
+    const TAG_LEN: usize = 32;
+
     /// Компрессия с применением AES шифрования
     pub fn compress_aes(
         source_file: &str,
         result_file: &str,
         max_bits_count: usize,
         secret: &str,
-    ) -> std::io::Result<()> {
-        // Инициализируем объекты
-        let mut lzw_struct = Compress::new(max_bits_count as u8);
+    ) -> Result<()> {
+        let options = Options {
+            max_bits_count,
+            password: Some(secret.to_owned()),
+            ..Default::default()
+        };
+        compress_with(source_file, result_file, &options)
+    }
+    /// Декомпрессия с применением AES шифрования. Размер словаря берется из заголовка архива.
+    /// Файл результата создается только после проверки пароля и подлинности архива
+    pub fn decompress_aes(source_file: &str, result_file: &str, secret: &str) -> Result<()> {
         let mut reader = BufReader::new(File::open(source_file)?);
-        let mut writer = File::create(result_file)?;
+        let archive = open_aes(&mut reader, &mut derive::KeyCache::new(secret))?;
+        let mut writer = BufWriter::new(File::create(result_file)?);
+        unpack_aes(archive, &mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+    /// Сжимает и шифрует срез в памяти
+    pub fn compress_aes_bytes(data: &[u8], max_bits_count: usize, secret: &str) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        compress_aes_bytes_into(data, max_bits_count, secret, &mut out)?;
+        Ok(out)
+    }
+    /// Сжимает и шифрует срез в памяти, дописывая архив в конец out
+    pub fn compress_aes_bytes_into(
+        data: &[u8],
+        max_bits_count: usize,
+        secret: &str,
+        out: &mut Vec<u8>,
+    ) -> Result<()> {
+        let options = Options {
+            max_bits_count,
+            password: Some(secret.to_owned()),
+            ..Default::default()
+        };
+        compress_bytes_with(data, &options, out)
+    }
+    /// Расшифровывает и распаковывает архив, находящийся в памяти
+    pub fn decompress_aes_bytes(data: &[u8], secret: &str) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        decompress_aes_bytes_into(data, secret, &mut out)?;
+        Ok(out)
+    }
+    /// Расшифровывает и распаковывает архив, находящийся в памяти,
+    /// дописывая результат в конец out. Распаковка начинается только после
+    /// проверки подлинности архива
+    pub fn decompress_aes_bytes_into(data: &[u8], secret: &str, out: &mut Vec<u8>) -> Result<()> {
+        decompress_aes_stream(data, out, secret)
+    }
+    /// Расшифровывает и распаковывает архив из reader в writer. Архив
+    /// расшифровывается в память за один проход; в writer ничего
+    /// не записывается, пока не проверен код аутентификации
+    pub fn decompress_aes_stream<R: Read, W: Write>(
+        mut reader: R,
+        writer: &mut W,
+        secret: &str,
+    ) -> Result<()> {
+        let archive = open_aes(&mut reader, &mut derive::KeyCache::new(secret))?;
+        unpack_aes(archive, writer)
+    }
+    /// Поток, вычисляющий HMAC всех записываемых в него данных
+    struct MacWriter<'a, W: Write> {
+        writer: &'a mut W,
+        context: hmac::SigningContext,
+    }
+    impl<'a, W: Write> Write for MacWriter<'a, W> {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let len = self.writer.write(buf)?;
+            self.context.update(&buf[..len]);
+            Ok(len)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.writer.flush()
+        }
+    }
+    /// Компрессия с применением AES шифрования из reader в writer
+    fn compress_aes_stream<R: Read, W: Write>(
+        mut reader: R,
+        writer: &mut W,
+        header: Header,
+        kdf: &derive::Kdf,
+        keys: &derive::Keys,
+    ) -> Result<()> {
+        // Инициализируем объекты
+        let mut lzw_struct = Compress::new(&header)?;
+        let mut hasher = header.checksum.map(Hasher::new);
+        // Вектор инициализации свой для каждого потока
+        let iv: Block = rand::random();
+        // Инициализируем AES ключом и IV
+        let mut aes = AesCtx::with_iv(keys.cypher, iv);
+        // Все, что записывается в архив, учитывается в коде аутентификации
+        let mut writer = MacWriter {
+            writer,
+            context: hmac::SigningContext::with_key(&keys.mac),
+        };
+        // Заголовок архива
+        header.with_kdf(*kdf).write(&mut writer)?;
         // Промежуточный буфер для чтения
         let mut buf_read: Vec<u8> = vec![0u8; AES_BLOCKLEN];
         // Промежуточный буфер для записи
         // По наблюдением, не нужен более чем 50 байт
         let mut buf_write: Vec<u8> = Vec::with_capacity(50);
-        // Объекты шифрования
-        let key = derive::derive_key(secret);
-        let iv: Block = rand::random();
-        // Инициализируем AES ключом и IV
-        let mut aes = AesCtx::with_iv(key, iv);
-        // Пишем вектор инициализации в файл
+        // Пишем вектор инициализации и проверочное значение ключа в файл
         writer.write_all(&iv)?;
+        writer.write_all(&keys.check)?;
         // Цикл компрессии с шифрованием
         loop {
             let bytes_read = reader.read(&mut buf_read)?;
             if bytes_read == 0 {
                 break;
             }
-            buf_read.truncate(bytes_read);
-            lzw_struct.compress(buf_read.as_slice(), &mut buf_write)?;
+            if let Some(hasher) = hasher.as_mut() {
+                hasher.update(&buf_read[..bytes_read]);
+            }
+            lzw_struct.compress(&buf_read[..bytes_read], &mut buf_write)?;
             // Если в буфере набралось 128 бит (16 байт) для шифрования
             while buf_write.len() >= AES_BLOCKLEN {
                 let buf_aes: Vec<u8> = buf_write.drain(0..AES_BLOCKLEN).collect();
@@ -363,56 +996,89 @@ pub mod lzw {
         }
         // Получаем/шифруем остаток байт
         lzw_struct.last_bytes(&mut buf_write)?;
+        // Контрольная сумма шифруется вместе с кодами
+        if let Some(hasher) = hasher {
+            buf_write.extend_from_slice(&hasher.finish());
+        }
+        pad_block(&mut buf_write);
         aes.aes_cbc_encrypt_buffer(buf_write.as_slice(), &mut writer)?;
+        // Дописываем код аутентификации
+        let tag = writer.context.sign();
+        writer.writer.write_all(tag.as_ref())?;
         Ok(())
     }
-    /// Декомпрессия с применением AES шифрования
-    pub fn decompress_aes(
-        source_file: &str,
-        result_file: &str,
-        max_bits_count: usize,
-        secret: &str,
-    ) -> std::io::Result<()> {
-        // Инициализируем объекты
-        let mut lzw_struct = Decompress::new(max_bits_count as u8);
-        let mut reader = BufReader::new(File::open(source_file)?);
-        let mut writer = File::create(result_file)?;
-        // Промежуточный буфер для чтения
-        let mut buf_read: Vec<u8> = vec![0u8; AES_BLOCKLEN];
-        // Промежуточный буфер для записи
-        let mut buf_write: Vec<u8> = Vec::with_capacity(AES_BLOCKLEN);
-        // Считываем вектор инициализации из файла
+    /// Зашифрованный архив, расшифрованный в память и прошедший проверку подлинности
+    struct AesArchive {
+        header: Header,
+        // Открытый текст без дополнения: коды и контрольная сумма
+        plain: Vec<u8>,
+    }
+    /// Проверяет пароль, расшифровывает архив в память и проверяет код аутентификации.
+    /// Архив считывается один раз, поэтому проверяется именно тот шифротекст,
+    /// который был расшифрован. Открытый текст отдается только после проверки
+    fn open_aes<R: Read>(reader: &mut R, key_cache: &mut derive::KeyCache) -> Result<AesArchive> {
+        let header = read_header(reader, true)?;
+        // Считываем вектор инициализации и проверочное значение ключа из файла
         let mut iv: Block = Default::default();
         reader.read_exact(&mut iv)?;
-
-        // Получаем 128-битный ключ
-        let key = derive::derive_key(secret);
-        // Инициализируем AES ключом и IV
-        let mut aes = AesCtx::with_iv(key, iv);
-        // Читаем первый блок
-        if reader.read(&mut buf_read)? != AES_BLOCKLEN {
-            panic!("Файл поврежден!");
-        }
-        // Цикл декомпрессии с расшифровкой
-        while {
-            // Отправляем блок на расшифровку
-            aes.aes_cbc_decrypt_buffer(buf_read.as_slice(), &mut buf_write)?;
-            // Читаем очередной  блок
-            let bytes_read = reader.read(&mut buf_read)?;
-            // Если достигли конца файла (EOF)
+        let mut check = [0u8; derive::CHECK_LEN];
+        reader.read_exact(&mut check)?;
+        // Соль и количество итераций берутся из заголовка
+        let kdf = header
+            .kdf
+            .ok_or(Error::BadHeader("не заданы параметры получения ключа"))?;
+        let keys = key_cache.get(&kdf)?;
+        // Неверный пароль обнаруживается до проверки всего архива
+        constant_time::verify_slices_are_equal(&keys.check, &check)
+            .map_err(|_| Error::WrongPassword)?;
+        // HMAC вычисляется от заголовка, IV, проверочного значения и шифротекста
+        let mut context = hmac::SigningContext::with_key(&keys.mac);
+        let mut header_bytes = Vec::new();
+        header.write(&mut header_bytes)?;
+        context.update(&header_bytes);
+        context.update(&iv);
+        context.update(&check);
+        // Шифротекст занимает все место до кода аутентификации
+        let mut reader = Trailer::new(reader, TAG_LEN);
+        let mut aes = AesCtx::with_iv(keys.cypher, iv);
+        let mut plain = Vec::new();
+        let mut buf = [0u8; 512 * AES_BLOCKLEN];
+        loop {
+            let bytes_read = read_block(&mut reader, &mut buf)?;
+            // Шифротекст всегда состоит из целых блоков
+            if bytes_read % AES_BLOCKLEN != 0 {
+                return Err(Error::Truncated);
+            }
             if bytes_read == 0 {
-                // То значит, что это последний блок
-                while buf_write.last().unwrap() == &0u8 {
-                    // Удаляем замыкающие нули (в соответствии с "Zero padding")
-                    buf_write.pop();
-                }
+                break;
             }
-            // Распаковываем блок
-            lzw_struct.decompress(buf_write.as_slice(), &mut writer)?;
-            buf_write.clear();
-            // Если что-то считано - продолжаем работу
-            bytes_read != 0
-        } {}
-        Ok(())
+            context.update(&buf[..bytes_read]);
+            for block in buf[..bytes_read].chunks(AES_BLOCKLEN) {
+                aes.aes_cbc_decrypt_buffer(block, &mut plain)?;
+            }
+        }
+        constant_time::verify_slices_are_equal(context.sign().as_ref(), reader.trailer()?)
+            .map_err(|_| Error::AuthenticationFailed)?;
+        unpad_block(&mut plain)?;
+        Ok(AesArchive { header, plain })
+    }
+    /// Распаковывает проверенный архив в writer и сверяет контрольную сумму
+    fn unpack_aes<W: Write>(archive: AesArchive, writer: &mut W) -> Result<()> {
+        // Контрольная сумма занимает последние байты открытого текста
+        let checksum = archive.header.checksum;
+        let trailer = checksum.map_or(0, Checksum::output_len);
+        if archive.plain.len() < trailer {
+            return Err(Error::Truncated);
+        }
+        let (codes, sum) = archive.plain.split_at(archive.plain.len() - trailer);
+        let mut writer = HashWriter {
+            writer,
+            hasher: checksum.map(Hasher::new),
+        };
+        Decompress::new(&archive.header)?.decompress(codes, &mut writer)?;
+        match writer.hasher {
+            Some(hasher) => hasher.verify(sum),
+            None => Ok(()),
+        }
     }
 }
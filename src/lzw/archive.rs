@@ -0,0 +1,766 @@
+//! Многофайловый архив с оглавлением
+//!
+//! Формат: `MAGIC` (4 байта), версия (1 байт), данные записей, оглавление
+//! и концевик. Данные каждой записи — обычный самостоятельный поток `lzw`
+//! (заголовок, сжатые и, возможно, зашифрованные данные), поэтому записи
+//! распаковываются теми же `Compress`/`Decompress`, что и одиночные файлы.
+//!
+//! Оглавление: количество записей (4 байта), затем для каждой записи длина
+//! имени (2 байта), имя в UTF-8, тип записи (1 байт), права доступа (4 байта),
+//! время изменения (8 байт секунд и 4 байта наносекунд), исходный размер
+//! (8 байт), сжатый размер (8 байт), смещение данных от начала архива (8 байт)
+//! и CRC32 исходных данных (4 байта). Концевик: смещение оглавления (8 байт)
+//! и `MAGIC`. Все числа записываются в little-endian.
+//!
+//! У каталогов данных нет. Данные символической ссылки — путь, на который
+//! она указывает.
+use super::block::BlockEncoder;
+use super::derive::{self, KeyCache, Keys};
+use super::error::{Error, Result};
+use super::header::{Header, Kdf};
+use super::{compress_aes_stream, decompress_parallel, open_aes, unpack_aes};
+use super::{LzwEncoder, Options};
+use filetime::FileTime;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Сигнатура многофайлового архива
+pub const MAGIC: [u8; 4] = *b"LZA\x1a";
+/// Текущая версия формата многофайлового архива
+pub const VERSION: u8 = 1;
+// Размер концевика: смещение оглавления и сигнатура
+const FOOTER_LEN: usize = 8 + 4;
+// Права доступа записей, добавленных не из файловой системы
+const DEFAULT_MODE: u32 = 0o644;
+
+/// Тип записи архива
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    /// Обычный файл
+    File,
+    /// Каталог
+    Directory,
+    /// Символическая ссылка
+    Symlink,
+}
+
+impl EntryKind {
+    fn from_u8(kind: u8) -> Result<EntryKind> {
+        match kind {
+            0 => Ok(EntryKind::File),
+            1 => Ok(EntryKind::Directory),
+            2 => Ok(EntryKind::Symlink),
+            _ => Err(Error::BadHeader("неизвестный тип записи")),
+        }
+    }
+    fn to_u8(self) -> u8 {
+        match self {
+            EntryKind::File => 0,
+            EntryKind::Directory => 1,
+            EntryKind::Symlink => 2,
+        }
+    }
+}
+
+/// Запись архива
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Относительный путь, компоненты разделены `/`
+    pub name: String,
+    /// Тип записи
+    pub kind: EntryKind,
+    /// Права доступа (биты режима Unix)
+    pub mode: u32,
+    /// Время изменения: секунды от начала эпохи Unix
+    pub mtime: i64,
+    /// Наносекунды времени изменения
+    pub mtime_nanos: u32,
+    /// Исходный размер в байтах
+    pub size: u64,
+    /// Размер сжатого потока в байтах
+    pub compressed_size: u64,
+    /// Смещение сжатого потока от начала архива
+    pub offset: u64,
+    /// CRC32 исходных данных
+    pub crc32: u32,
+}
+
+impl Entry {
+    fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(&(self.name.len() as u16).to_le_bytes())?;
+        writer.write_all(self.name.as_bytes())?;
+        writer.write_all(&[self.kind.to_u8()])?;
+        writer.write_all(&self.mode.to_le_bytes())?;
+        writer.write_all(&self.mtime.to_le_bytes())?;
+        writer.write_all(&self.mtime_nanos.to_le_bytes())?;
+        writer.write_all(&self.size.to_le_bytes())?;
+        writer.write_all(&self.compressed_size.to_le_bytes())?;
+        writer.write_all(&self.offset.to_le_bytes())?;
+        writer.write_all(&self.crc32.to_le_bytes())?;
+        Ok(())
+    }
+    fn read<R: Read>(reader: &mut R) -> Result<Entry> {
+        let mut name = vec![0u8; read_u16(reader)? as usize];
+        reader.read_exact(&mut name)?;
+        let name = String::from_utf8(name).map_err(|err| {
+            Error::InvalidEntryName(String::from_utf8_lossy(err.as_bytes()).into())
+        })?;
+        check_name(&name)?;
+        let mut kind = [0u8; 1];
+        reader.read_exact(&mut kind)?;
+        Ok(Entry {
+            name,
+            kind: EntryKind::from_u8(kind[0])?,
+            mode: read_u32(reader)?,
+            mtime: read_u64(reader)? as i64,
+            mtime_nanos: read_u32(reader)?,
+            size: read_u64(reader)?,
+            compressed_size: read_u64(reader)?,
+            offset: read_u64(reader)?,
+            crc32: read_u32(reader)?,
+        })
+    }
+}
+
+/// Создает многофайловый архив в заданном потоке.
+///
+/// Все записи сжимаются с одними параметрами. При шифровании ключ получается
+/// из пароля один раз, и все записи используют одну соль.
+/// После добавления записей необходимо вызвать `finish`, иначе оглавление
+/// не будет записано.
+pub struct ArchiveWriter<W: Write> {
+    writer: Counter<BufWriter<W>>,
+    // Заголовок каждого сжатого потока, без шифрования и блоков
+    header: Header,
+    // Размер блока и количество потоков, если записи сжимаются блоками
+    blocks: Option<(usize, usize)>,
+    // Параметры и ключи шифрования
+    crypto: Option<(Kdf, Keys)>,
+    // Файлы, которые не добавляются при обходе каталогов (канонические пути)
+    excluded: Vec<PathBuf>,
+    entries: Vec<Entry>,
+}
+
+impl<W: Write> ArchiveWriter<W> {
+    /// Создает архив и записывает его сигнатуру
+    pub fn new(writer: W, options: &Options) -> Result<Self> {
+        let header = options.header()?;
+        let blocks = options
+            .blocks()?
+            .map(|block_size| (block_size, options.threads));
+        let crypto = match &options.password {
+            Some(secret) => {
+                let kdf = derive::new_kdf(options.kdf_iterations)?;
+                let keys = derive::derive_keys(secret, &kdf)?;
+                Some((kdf, keys))
+            }
+            None => None,
+        };
+        let mut writer = Counter::new(BufWriter::new(writer));
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        Ok(ArchiveWriter {
+            writer,
+            header,
+            blocks,
+            crypto,
+            excluded: Vec::new(),
+            entries: Vec::new(),
+        })
+    }
+    /// Исключает файл из обхода каталогов в `add_path`, как tar исключает
+    /// сам архив: иначе архив, записываемый в сжимаемый каталог, читал бы
+    /// сам себя. Файл может еще не существовать
+    pub fn exclude<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let path = match fs::canonicalize(path) {
+            Ok(path) => path,
+            // Файл еще не создан: канонический путь собирается из каталога
+            Err(_) => {
+                let parent = match path.parent() {
+                    Some(parent) if !parent.as_os_str().is_empty() => parent,
+                    _ => Path::new("."),
+                };
+                let name = path
+                    .file_name()
+                    .ok_or_else(|| Error::InvalidEntryName(path.display().to_string()))?;
+                fs::canonicalize(parent)?.join(name)
+            }
+        };
+        self.excluded.push(path);
+        Ok(())
+    }
+    /// Записи, добавленные в архив
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+    /// Сжимает данные из reader в файл архива с заданным именем.
+    /// Права доступа по умолчанию, время изменения — текущее
+    pub fn add<R: Read>(&mut self, name: &str, reader: R) -> Result<()> {
+        let mtime = FileTime::from_system_time(SystemTime::now());
+        self.add_entry(name, EntryKind::File, DEFAULT_MODE, mtime, Some(reader))
+    }
+    /// Добавляет в архив файл, символическую ссылку или рекурсивно каталог
+    /// вместе с правами доступа и временем изменения.
+    /// Имена записей начинаются с последнего компонента path
+    pub fn add_path<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let name = match path.file_name() {
+            Some(name) => os_name(name)?,
+            // Для `.` и `/` записывается только содержимое каталога
+            None => String::new(),
+        };
+        self.add_tree(path, name)
+    }
+    fn add_tree(&mut self, path: &Path, name: String) -> Result<()> {
+        // Ссылки сохраняются как есть, без перехода по ним
+        let meta = fs::symlink_metadata(path)?;
+        let mode = file_mode(&meta);
+        let mtime = FileTime::from_last_modification_time(&meta);
+        if meta.file_type().is_symlink() {
+            let target = fs::read_link(path)?;
+            let target = os_name(target.as_os_str())?;
+            return self.add_entry(
+                &name,
+                EntryKind::Symlink,
+                mode,
+                mtime,
+                Some(target.as_bytes()),
+            );
+        }
+        if !meta.is_dir() {
+            if !self.excluded.is_empty() && self.excluded.contains(&fs::canonicalize(path)?) {
+                return Ok(());
+            }
+            let reader = File::open(path)?;
+            return self.add_entry(&name, EntryKind::File, mode, mtime, Some(reader));
+        }
+        // Каталог записывается отдельной записью, чтобы сохранить пустые
+        // каталоги и их атрибуты
+        if !name.is_empty() {
+            self.add_entry(&name, EntryKind::Directory, mode, mtime, None::<File>)?;
+        }
+        // Порядок записей не должен зависеть от файловой системы
+        let mut children = fs::read_dir(path)?
+            .map(|child| child.map(|child| child.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        children.sort();
+        for child in children {
+            let child_name = os_name(child.file_name().unwrap())?;
+            let child_name = if name.is_empty() {
+                child_name
+            } else {
+                format!("{}/{}", name, child_name)
+            };
+            self.add_tree(&child, child_name)?;
+        }
+        Ok(())
+    }
+    fn add_entry<R: Read>(
+        &mut self,
+        name: &str,
+        kind: EntryKind,
+        mode: u32,
+        mtime: FileTime,
+        reader: Option<R>,
+    ) -> Result<()> {
+        check_name(name)?;
+        if self.entries.iter().any(|entry| entry.name == name) {
+            return Err(Error::InvalidEntryName(name.to_owned()));
+        }
+        let offset = self.writer.count;
+        let (size, crc32) = match reader {
+            Some(reader) => self.write_stream(reader)?,
+            None => (0, 0),
+        };
+        self.entries.push(Entry {
+            name: name.to_owned(),
+            kind,
+            mode,
+            mtime: mtime.unix_seconds(),
+            mtime_nanos: mtime.nanoseconds(),
+            size,
+            compressed_size: self.writer.count - offset,
+            offset,
+            crc32,
+        });
+        Ok(())
+    }
+    /// Сжимает данные из reader в отдельный поток `lzw`.
+    /// Возвращает исходный размер и CRC32 данных
+    fn write_stream<R: Read>(&mut self, reader: R) -> Result<(u64, u32)> {
+        let mut reader = Crc::new(reader);
+        if let Some((block_size, threads)) = self.blocks {
+            let mut encoder =
+                BlockEncoder::new(&mut self.writer, self.header, block_size, threads)?;
+            io::copy(&mut reader, &mut encoder)?;
+            encoder.finish()?;
+            return Ok((reader.count, reader.hasher.finalize()));
+        }
+        match &self.crypto {
+            Some((kdf, keys)) => compress_aes_stream(
+                BufReader::new(&mut reader),
+                &mut self.writer,
+                self.header,
+                kdf,
+                keys,
+            )?,
+            None => {
+                let mut encoder = LzwEncoder::with_header(&mut self.writer, self.header)?;
+                io::copy(&mut reader, &mut encoder)?;
+                encoder.finish()?;
+            }
+        }
+        Ok((reader.count, reader.hasher.finalize()))
+    }
+    /// Записывает оглавление и концевик, возвращает вложенный поток
+    pub fn finish(mut self) -> Result<W> {
+        let directory_offset = self.writer.count;
+        self.writer
+            .write_all(&(self.entries.len() as u32).to_le_bytes())?;
+        for entry in &self.entries {
+            entry.write(&mut self.writer)?;
+        }
+        self.writer.write_all(&directory_offset.to_le_bytes())?;
+        self.writer.write_all(&MAGIC)?;
+        Ok(self
+            .writer
+            .writer
+            .into_inner()
+            .map_err(|err| err.into_error())?)
+    }
+}
+
+/// Читает многофайловый архив
+pub struct ArchiveReader<R: Read + Seek> {
+    reader: R,
+    entries: Vec<Entry>,
+    key_cache: Option<KeyCache>,
+    // Количество потоков распаковки блочных записей
+    threads: usize,
+    // Восстанавливать ли биты setuid, setgid и sticky
+    special_bits: bool,
+}
+
+impl<R: Read + Seek> ArchiveReader<R> {
+    /// Проверяет сигнатуру и считывает оглавление архива
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut magic = [0u8; 5];
+        reader.seek(SeekFrom::Start(0))?;
+        reader.read_exact(&mut magic)?;
+        if magic[..MAGIC.len()] != MAGIC {
+            return Err(Error::BadHeader("файл не является многофайловым архивом"));
+        }
+        if magic[4] != VERSION {
+            return Err(Error::BadHeader("неподдерживаемая версия формата"));
+        }
+        let end = reader.seek(SeekFrom::End(-(FOOTER_LEN as i64)))?;
+        let directory_offset = read_u64(&mut reader)?;
+        reader.read_exact(&mut magic[..MAGIC.len()])?;
+        if magic[..MAGIC.len()] != MAGIC || directory_offset > end {
+            return Err(Error::BadHeader("оглавление архива повреждено"));
+        }
+        reader.seek(SeekFrom::Start(directory_offset))?;
+        let count = read_u32(&mut reader)?;
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            let entry = Entry::read(&mut reader)?;
+            if entry.offset < MAGIC.len() as u64 + 1
+                || entry.offset.checked_add(entry.compressed_size).is_none()
+                || entry.offset + entry.compressed_size > directory_offset
+            {
+                return Err(Error::BadHeader("оглавление архива повреждено"));
+            }
+            entries.push(entry);
+        }
+        Ok(ArchiveReader {
+            reader,
+            entries,
+            key_cache: None,
+            threads: 1,
+            special_bits: false,
+        })
+    }
+    /// Записи архива
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+    /// Запись с заданным номером
+    pub fn entry(&self, index: usize) -> Result<&Entry> {
+        self.entries.get(index).ok_or(Error::NoSuchEntry(index))
+    }
+    /// Задает пароль для зашифрованных записей
+    pub fn set_password(&mut self, password: &str) {
+        self.key_cache = Some(KeyCache::new(password));
+    }
+    /// Задает количество потоков, в которых распаковываются блоки записей,
+    /// сжатых блоками
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads;
+    }
+    /// Разрешает `extract_all` восстанавливать биты setuid, setgid и sticky.
+    /// По умолчанию они сбрасываются, как в tar: архив может прийти
+    /// из недоверенного источника
+    pub fn set_special_bits(&mut self, restore: bool) {
+        self.special_bits = restore;
+    }
+    /// Заголовок сжатого потока записи
+    pub fn header(&mut self, index: usize) -> Result<Header> {
+        let offset = self.entry(index)?.offset;
+        self.reader.seek(SeekFrom::Start(offset))?;
+        Header::read(&mut self.reader)
+    }
+    /// Распаковывает запись с заданным номером в writer и проверяет ее CRC32
+    pub fn extract<W: Write>(&mut self, index: usize, writer: &mut W) -> Result<()> {
+        let entry = self.entry(index)?.clone();
+        // У каталогов нет данных
+        if entry.kind == EntryKind::Directory {
+            return Ok(());
+        }
+        let mut section = Section::new(&mut self.reader, entry.offset, entry.compressed_size)?;
+        let mut writer = Crc::new(writer);
+        match &mut self.key_cache {
+            Some(key_cache) => {
+                let archive = open_aes(&mut section, key_cache)?;
+                unpack_aes(archive, &mut writer)?;
+            }
+            None => decompress_parallel(section, &mut writer, self.threads)?,
+        }
+        if writer.count != entry.size || writer.hasher.clone().finalize() != entry.crc32 {
+            return Err(Error::ChecksumMismatch);
+        }
+        Ok(())
+    }
+    /// Распаковывает все записи в каталог dest, восстанавливая каталоги,
+    /// символические ссылки, права доступа и время изменения
+    pub fn extract_all<P: AsRef<Path>>(&mut self, dest: P) -> Result<()> {
+        let dest = dest.as_ref();
+        let mut directories = Vec::new();
+        let mut symlinks = Vec::new();
+        // Сначала распаковываются данные: при неверном пароле на диске ничего
+        // не появится. Ссылки создаются после файлов, чтобы запись файла
+        // не могла пройти по ссылке из архива за пределы dest. Ссылки,
+        // оставшиеся от прежних распаковок, отвергает check_path
+        for index in 0..self.entries.len() {
+            let entry = self.entries[index].clone();
+            let path = dest.join(&entry.name);
+            match entry.kind {
+                EntryKind::File => {
+                    check_path(dest, &entry.name, false)?;
+                    let mut writer = LazyFile {
+                        path: path.clone(),
+                        file: None,
+                    };
+                    self.extract(index, &mut writer)?;
+                    writer.finish()?;
+                    set_mode(&path, self.entry_mode(&entry))?;
+                    filetime::set_file_mtime(&path, entry_mtime(&entry))?;
+                }
+                EntryKind::Symlink => {
+                    let mut target = Vec::new();
+                    self.extract(index, &mut target)?;
+                    let target = String::from_utf8(target)
+                        .map_err(|_| Error::InvalidEntryName(entry.name.clone()))?;
+                    check_target(&entry.name, &target)?;
+                    symlinks.push((path, target, entry));
+                }
+                EntryKind::Directory => directories.push((path, entry)),
+            }
+        }
+        for (path, entry) in &directories {
+            check_path(dest, &entry.name, true)?;
+            fs::create_dir_all(path)?;
+        }
+        for (path, target, entry) in symlinks {
+            check_path(dest, &entry.name, false)?;
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            // Существующий файл заменяется, как и при распаковке файлов
+            if fs::symlink_metadata(&path).is_ok_and(|meta| !meta.is_dir()) {
+                fs::remove_file(&path)?;
+            }
+            create_symlink(&target, &path)?;
+            let mtime = entry_mtime(&entry);
+            filetime::set_symlink_file_times(&path, mtime, mtime)?;
+        }
+        // Атрибуты каталогов восстанавливаются последними и от вложенных к внешним:
+        // запись в каталог меняет его время изменения, а права могут ее запрещать
+        for (path, entry) in directories.iter().rev() {
+            set_mode(path, self.entry_mode(entry))?;
+            filetime::set_file_mtime(path, entry_mtime(entry))?;
+        }
+        Ok(())
+    }
+    /// Права доступа, которые получит распакованная запись
+    fn entry_mode(&self, entry: &Entry) -> u32 {
+        if self.special_bits {
+            entry.mode & 0o7777
+        } else {
+            entry.mode & 0o777
+        }
+    }
+}
+
+fn entry_mtime(entry: &Entry) -> FileTime {
+    FileTime::from_unix_time(entry.mtime, entry.mtime_nanos)
+}
+#[cfg(unix)]
+fn file_mode(meta: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode() & 0o7777
+}
+#[cfg(not(unix))]
+fn file_mode(meta: &fs::Metadata) -> u32 {
+    let mode = if meta.is_dir() { 0o755 } else { DEFAULT_MODE };
+    if meta.permissions().readonly() {
+        mode & !0o222
+    } else {
+        mode
+    }
+}
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+#[cfg(not(unix))]
+fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    let mut permissions = fs::metadata(path)?.permissions();
+    permissions.set_readonly(mode & 0o200 == 0);
+    fs::set_permissions(path, permissions)
+}
+#[cfg(unix)]
+fn create_symlink(target: &str, path: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, path)
+}
+/// Там, где ссылки недоступны, ссылка распаковывается в файл с путем к цели
+#[cfg(not(unix))]
+fn create_symlink(target: &str, path: &Path) -> io::Result<()> {
+    fs::write(path, target)
+}
+
+/// Проверяет, что имя записи — относительный путь, не выходящий за пределы
+/// каталога распаковки
+fn check_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= u16::MAX as usize
+        && !name.contains('\\')
+        && !name.contains('\0')
+        && name
+            .split('/')
+            .all(|part| !part.is_empty() && part != "." && part != "..");
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::InvalidEntryName(name.to_owned()))
+    }
+}
+/// Проверяет, что относительная цель ссылки name не выходит за пределы
+/// каталога распаковки. Переходы `..` допускаются только в начале цели:
+/// после обычного компонента они могли бы пройти через другую ссылку
+fn check_target(name: &str, target: &str) -> Result<()> {
+    // Глубина каталога, в котором лежит ссылка
+    let mut depth = name.matches('/').count();
+    let mut leading = true;
+    let valid = !target.is_empty()
+        && !target.starts_with('/')
+        && !target.contains('\\')
+        && !target.contains('\0')
+        && target.split('/').all(|part| match part {
+            "" | "." => true,
+            ".." if leading && depth > 0 => {
+                depth -= 1;
+                true
+            }
+            ".." => false,
+            _ => {
+                leading = false;
+                true
+            }
+        });
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::UnsafeSymlink(name.to_owned()))
+    }
+}
+/// Проверяет, что путь к записи name внутри dest не проходит через
+/// символические ссылки: запись по такому пути попала бы туда, куда указывает
+/// ссылка. Последний компонент пути проверяется, если last
+fn check_path(dest: &Path, name: &str, last: bool) -> Result<()> {
+    let parts: Vec<&str> = name.split('/').collect();
+    let count = if last { parts.len() } else { parts.len() - 1 };
+    let mut path = dest.to_path_buf();
+    for part in &parts[..count] {
+        path.push(part);
+        if fs::symlink_metadata(&path).is_ok_and(|meta| meta.file_type().is_symlink()) {
+            return Err(Error::UnsafeSymlink(name.to_owned()));
+        }
+    }
+    Ok(())
+}
+/// Имя файла в виде строки UTF-8
+fn os_name(name: &std::ffi::OsStr) -> Result<String> {
+    name.to_str()
+        .map(String::from)
+        .ok_or_else(|| Error::InvalidEntryName(name.to_string_lossy().into()))
+}
+fn read_u16<R: Read>(reader: &mut R) -> Result<u16> {
+    let mut buf = [0u8; 2];
+    reader.read_exact(&mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+/// Поток, считающий записанные байты
+struct Counter<W: Write> {
+    writer: W,
+    count: u64,
+}
+impl<W: Write> Counter<W> {
+    fn new(writer: W) -> Self {
+        Counter { writer, count: 0 }
+    }
+}
+impl<W: Write> Write for Counter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.writer.write(buf)?;
+        self.count += len as u64;
+        Ok(len)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Поток, вычисляющий CRC32 и размер проходящих через него данных
+struct Crc<T> {
+    inner: T,
+    hasher: crc32fast::Hasher,
+    count: u64,
+}
+impl<T> Crc<T> {
+    fn new(inner: T) -> Self {
+        Crc {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+            count: 0,
+        }
+    }
+}
+impl<R: Read> Read for Crc<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.hasher.update(&buf[..len]);
+        self.count += len as u64;
+        Ok(len)
+    }
+}
+impl<W: Write> Write for Crc<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.hasher.update(&buf[..len]);
+        self.count += len as u64;
+        Ok(len)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Файл, который создается (вместе с родительскими каталогами) только при
+/// первой записи. Если пароль неверен или архив поврежден до начала данных,
+/// на диске ничего не остается
+struct LazyFile {
+    path: PathBuf,
+    file: Option<BufWriter<File>>,
+}
+impl LazyFile {
+    fn file(&mut self) -> io::Result<&mut BufWriter<File>> {
+        if self.file.is_none() {
+            if let Some(parent) = self.path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            // Файл заменяет ссылку на своем месте, а не пишет по ней
+            if fs::symlink_metadata(&self.path).is_ok_and(|meta| meta.file_type().is_symlink()) {
+                fs::remove_file(&self.path)?;
+            }
+            self.file = Some(BufWriter::new(File::create(&self.path)?));
+        }
+        Ok(self.file.as_mut().unwrap())
+    }
+    /// Сбрасывает буфер; пустой файл создается здесь
+    fn finish(mut self) -> io::Result<()> {
+        self.file()?.flush()
+    }
+}
+impl Write for LazyFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file()?.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.file {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Участок потока [start, start + len), доступный для чтения и перемещения
+/// как отдельный поток
+struct Section<'a, R: Read + Seek> {
+    reader: &'a mut R,
+    start: u64,
+    len: u64,
+    pos: u64,
+}
+impl<'a, R: Read + Seek> Section<'a, R> {
+    fn new(reader: &'a mut R, start: u64, len: u64) -> io::Result<Self> {
+        reader.seek(SeekFrom::Start(start))?;
+        Ok(Section {
+            reader,
+            start,
+            len,
+            pos: 0,
+        })
+    }
+}
+impl<'a, R: Read + Seek> Read for Section<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let max = (buf.len() as u64).min(self.len - self.pos) as usize;
+        let len = self.reader.read(&mut buf[..max])?;
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+impl<'a, R: Read + Seek> Seek for Section<'a, R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => pos as i64,
+            SeekFrom::End(offset) => self.len as i64 + offset,
+            SeekFrom::Current(offset) => self.pos as i64 + offset,
+        };
+        if pos < 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "перемещение до начала участка",
+            ));
+        }
+        // За концом участка читать нечего, как и за концом файла
+        self.pos = (pos as u64).min(self.len);
+        self.reader.seek(SeekFrom::Start(self.start + self.pos))?;
+        Ok(self.pos)
+    }
+}
@@ -0,0 +1,277 @@
+//! Упаковка кодов переменной длины в поток байт
+//!
+//! Коды накапливаются в 64-битном регистре: записываются по 4 байта,
+//! а читаются порциями до 8 байт. Биты, считанные сверх текущего кода,
+//! остаются в регистре до следующего вызова. Поток передается в каждый
+//! вызов: так состояние можно хранить между порциями данных, например
+//! между блоками AES.
+use std::io::{self, Read, Write};
+
+/// Порядок бит в байте
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitOrder {
+    /// Старшим битом вперед: основной формат, TIFF/PDF
+    Msb,
+    /// Младшим битом вперед: GIF, Shrink
+    Lsb,
+}
+
+/// Наибольшая длина одного кода в битах
+pub const MAX_CODE_BITS: u8 = 32;
+
+/// Маска из bits младших бит
+fn mask(bits: u8) -> u64 {
+    (1 << bits) - 1
+}
+
+/// Упаковщик кодов
+#[derive(Debug, Clone)]
+pub struct BitWriter {
+    order: BitOrder,
+    // Накопленные, но еще не записанные биты: младшие len бит регистра
+    acc: u64,
+    len: u8,
+}
+
+impl BitWriter {
+    pub fn new(order: BitOrder) -> Self {
+        BitWriter {
+            order,
+            acc: 0,
+            len: 0,
+        }
+    }
+    /// Добавляет код из bits младших бит value. Полные 4 байта записываются в writer
+    pub fn write_bits<W: Write>(&mut self, writer: &mut W, value: u32, bits: u8) -> io::Result<()> {
+        debug_assert!(bits <= MAX_CODE_BITS);
+        let value = u64::from(value) & mask(bits);
+        match self.order {
+            BitOrder::Msb => self.acc = (self.acc << bits) | value,
+            BitOrder::Lsb => self.acc |= value << self.len,
+        }
+        self.len += bits;
+        if self.len >= 32 {
+            self.len -= 32;
+            let word = match self.order {
+                BitOrder::Msb => ((self.acc >> self.len) as u32).to_be_bytes(),
+                BitOrder::Lsb => {
+                    let word = (self.acc as u32).to_le_bytes();
+                    self.acc >>= 32;
+                    word
+                }
+            };
+            writer.write_all(&word)?;
+        }
+        Ok(())
+    }
+    /// Записывает все накопленные биты, дополняя последний байт нулями
+    pub fn pad<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        let bytes = (self.len as usize).div_ceil(8);
+        let mut buf = [0u8; 4];
+        match self.order {
+            BitOrder::Msb => {
+                let acc = (self.acc & mask(self.len)) << (bytes * 8 - self.len as usize);
+                for (i, byte) in buf[..bytes].iter_mut().rev().enumerate() {
+                    *byte = (acc >> (i * 8)) as u8;
+                }
+            }
+            BitOrder::Lsb => {
+                for (i, byte) in buf[..bytes].iter_mut().enumerate() {
+                    *byte = (self.acc >> (i * 8)) as u8;
+                }
+            }
+        }
+        writer.write_all(&buf[..bytes])?;
+        self.acc = 0;
+        self.len = 0;
+        Ok(())
+    }
+}
+
+/// Распаковщик кодов
+#[derive(Debug, Clone)]
+pub struct BitReader {
+    order: BitOrder,
+    // Считанные, но еще не отданные биты: младшие len бит регистра
+    acc: u64,
+    len: u8,
+}
+
+impl BitReader {
+    pub fn new(order: BitOrder) -> Self {
+        BitReader {
+            order,
+            acc: 0,
+            len: 0,
+        }
+    }
+    /// Считывает код длиной bits. Если поток закончился раньше, возвращает None;
+    /// считанные биты при этом сохраняются до следующего вызова
+    pub fn read_bits<R: Read>(&mut self, reader: &mut R, bits: u8) -> io::Result<Option<u32>> {
+        debug_assert!(bits <= MAX_CODE_BITS);
+        while self.len < bits {
+            // Регистр дополняется за одно чтение столькими байтами, сколько в нем поместится
+            let mut buf = [0u8; 8];
+            let room = usize::from((64 - self.len) / 8);
+            let count = reader.read(&mut buf[..room])?;
+            if count == 0 {
+                return Ok(None);
+            }
+            for &byte in &buf[..count] {
+                match self.order {
+                    BitOrder::Msb => self.acc = (self.acc << 8) | u64::from(byte),
+                    BitOrder::Lsb => self.acc |= u64::from(byte) << self.len,
+                }
+                self.len += 8;
+            }
+        }
+        self.len -= bits;
+        let value = match self.order {
+            BitOrder::Msb => self.acc >> self.len,
+            BitOrder::Lsb => {
+                let value = self.acc;
+                self.acc >>= bits;
+                value
+            }
+        };
+        Ok(Some((value & mask(bits)) as u32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(order: BitOrder, codes: &[(u32, u8)]) -> Vec<u8> {
+        let mut writer = BitWriter::new(order);
+        let mut out = Vec::new();
+        for &(value, bits) in codes {
+            writer.write_bits(&mut out, value, bits).unwrap();
+        }
+        writer.pad(&mut out).unwrap();
+        out
+    }
+    fn read(order: BitOrder, data: &[u8], widths: &[u8]) -> Vec<Option<u32>> {
+        let mut reader = BitReader::new(order);
+        let mut data = data;
+        widths
+            .iter()
+            .map(|&bits| reader.read_bits(&mut data, bits).unwrap())
+            .collect()
+    }
+    /// Псевдослучайные коды разной длины
+    fn random_codes(count: usize) -> Vec<(u32, u8)> {
+        let mut state: u32 = 12345;
+        (0..count)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let bits = (state >> 27) as u8 + 1;
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> (32 - bits), bits)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn msb_vector_test() {
+        // 101 | 1100_0000_1 | 1: старшим битом вперед
+        let codes = [(0b101, 3), (0b1_1000_0001, 9), (1, 1)];
+        assert_eq!(write(BitOrder::Msb, &codes), vec![0b1011_1000, 0b0001_1000]);
+        assert_eq!(
+            read(BitOrder::Msb, &[0b1011_1000, 0b0001_1000], &[3, 9, 1, 3]),
+            vec![Some(0b101), Some(0b1_1000_0001), Some(1), Some(0)]
+        );
+        // Лишние старшие биты значения отбрасываются
+        assert_eq!(write(BitOrder::Msb, &[(0xFFFF_FF01, 8)]), vec![0x01]);
+    }
+    #[test]
+    fn lsb_vector_test() {
+        // Те же коды младшим битом вперед, как в GIF
+        let codes = [(0b101, 3), (0b1_1000_0001, 9), (1, 1)];
+        assert_eq!(write(BitOrder::Lsb, &codes), vec![0b0000_1101, 0b0001_1100]);
+        assert_eq!(
+            read(BitOrder::Lsb, &[0b0000_1101, 0b0001_1100], &[3, 9, 1, 3]),
+            vec![Some(0b101), Some(0b1_1000_0001), Some(1), Some(0)]
+        );
+    }
+    #[test]
+    fn round_trip_test() {
+        for &order in &[BitOrder::Msb, BitOrder::Lsb] {
+            // Все допустимые длины кода
+            for bits in 1..=MAX_CODE_BITS {
+                let max = (1u64 << bits) - 1;
+                let codes: Vec<(u32, u8)> = (0..100u64)
+                    .map(|i| (((i * 0x9E37_79B9) & max) as u32, bits))
+                    .collect();
+                let data = write(order, &codes);
+                assert_eq!(data.len(), (100 * bits as usize).div_ceil(8));
+                let widths: Vec<u8> = codes.iter().map(|&(_, bits)| bits).collect();
+                let values: Vec<Option<u32>> =
+                    codes.iter().map(|&(value, _)| Some(value)).collect();
+                assert_eq!(read(order, &data, &widths), values);
+            }
+            // Длина меняется от кода к коду
+            let codes = random_codes(10_000);
+            let data = write(order, &codes);
+            let widths: Vec<u8> = codes.iter().map(|&(_, bits)| bits).collect();
+            let values: Vec<Option<u32>> = codes.iter().map(|&(value, _)| Some(value)).collect();
+            assert_eq!(read(order, &data, &widths), values);
+        }
+    }
+    #[test]
+    fn pad_test() {
+        for &order in &[BitOrder::Msb, BitOrder::Lsb] {
+            // Пустой буфер ничего не дописывает
+            assert!(write(order, &[]).is_empty());
+            // Неполный байт дополняется нулями
+            assert_eq!(write(order, &[(0, 1)]), vec![0]);
+            assert_eq!(write(order, &[(u32::MAX, 32)]), vec![0xFF; 4]);
+            assert_eq!(write(order, &[(u32::MAX, 32), (0, 1)]).len(), 5);
+            // После pad запись продолжается с границы байта
+            let mut writer = BitWriter::new(order);
+            let mut out = Vec::new();
+            writer.write_bits(&mut out, 1, 1).unwrap();
+            writer.pad(&mut out).unwrap();
+            writer.write_bits(&mut out, 0xAB, 8).unwrap();
+            writer.pad(&mut out).unwrap();
+            assert_eq!(out[1], 0xAB);
+        }
+    }
+    #[test]
+    fn eof_test() {
+        for &order in &[BitOrder::Msb, BitOrder::Lsb] {
+            let data = write(order, &[(0x3FF, 10), (0x155, 10)]);
+            assert_eq!(data.len(), 3);
+            let mut reader = BitReader::new(order);
+            // Второй код не помещается в первые два байта: считанные
+            // биты остаются в буфере и дополняются из следующей порции
+            let mut first = &data[..2];
+            assert_eq!(reader.read_bits(&mut first, 10).unwrap(), Some(0x3FF));
+            assert_eq!(reader.read_bits(&mut first, 10).unwrap(), None);
+            let mut second = &data[2..];
+            assert_eq!(reader.read_bits(&mut second, 10).unwrap(), Some(0x155));
+            // Остаток последнего байта не образует кода
+            assert_eq!(reader.read_bits(&mut second, 10).unwrap(), None);
+        }
+    }
+    #[test]
+    fn short_read_test() {
+        // Поток отдает по одному байту за вызов read: регистр дополняется частями
+        struct ByteByByte<'a>(&'a [u8]);
+        impl<'a> Read for ByteByByte<'a> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let len = buf.len().min(1);
+                self.0.read(&mut buf[..len])
+            }
+        }
+        for &order in &[BitOrder::Msb, BitOrder::Lsb] {
+            let codes = random_codes(1000);
+            let data = write(order, &codes);
+            let mut source = ByteByByte(&data);
+            let mut reader = BitReader::new(order);
+            for &(value, bits) in &codes {
+                assert_eq!(reader.read_bits(&mut source, bits).unwrap(), Some(value));
+            }
+        }
+    }
+}
@@ -0,0 +1,576 @@
+//! Блочное сжатие в несколько потоков
+//!
+//! Вход разбивается на блоки по `Options::block_size` байт, и каждый блок
+//! сжимается своим словарем. Блоки не зависят друг от друга, поэтому
+//! сжимаются и распаковываются параллельно, в пуле потоков; степень сжатия
+//! при этом немного ниже, так как каждый словарь начинается заново.
+//!
+//! Формат данных после заголовка с `FLAG_BLOCKS`: блоки, каждый — исходный
+//! размер (4 байта), размер кодов (4 байта) и коды, дополненные нулями
+//! до целого байта. Блок нулевого размера завершает последовательность,
+//! так что архив можно распаковать и не перемещаясь по нему. За ним
+//! следует контрольная сумма, если она хранится в архиве.
+//! Далее индекс блоков: их количество (4 байта) и для каждого блока
+//! смещение в исходных данных (8 байт), смещение первого кода в битах
+//! от начала архива (8 байт) и количество точек входа внутри блока
+//! (4 байта), за которым следуют сами точки. Концевик: смещение индекса
+//! от начала архива (8 байт) и `MAGIC`. Все числа записываются в little-endian.
+//!
+//! Точка входа — место внутри блока, где словарь сбрасывается и распаковку
+//! можно начать, не распаковывая блок с начала: смещение в исходных данных
+//! (8 байт), смещение в битах от начала архива (8 байт), длина строки,
+//! которую продолжит первая строка нового словаря (4 байта), и сама строка.
+//! В режиме `DictionaryMode::Reset` это последнее кодовое слово сброшенного
+//! словаря, после кода CLEAR строка пустая; в режиме LZT словарь
+//! не сбрасывается, и точек входа внутри блоков нет. Их нет и при
+//! энтропийном кодировании: состояние кодера после сброса словаря
+//! не восстановить, не декодировав блок с начала.
+use super::checksum::{Checksum, Hasher};
+use super::error::{Error, Result};
+use super::header::{Header, FLAG_BLOCKS};
+use super::{Compress, Decompress};
+use std::collections::BTreeMap;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// Размер блока по умолчанию
+pub const BLOCK_SIZE: usize = 4 << 20;
+/// Наибольший размер блока: коды блока должны поместиться в 4 байта размера
+pub const MAX_BLOCK_SIZE: usize = 256 << 20;
+/// Наибольшее количество потоков
+pub const MAX_THREADS: usize = 256;
+/// Сигнатура концевика блочного архива
+pub const MAGIC: [u8; 4] = *b"LZB\x1a";
+// Размер заголовка блока: исходный размер и размер кодов
+const BLOCK_HEADER_LEN: u64 = 4 + 4;
+// Размер концевика: смещение индекса и сигнатура
+const FOOTER_LEN: u64 = 8 + 4;
+
+/// Точка входа: с нее распаковка может начаться после сброса словаря
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Point {
+    // Смещение в исходных данных и в битах от начала архива
+    // (у кодировщика — от начала блока)
+    pub offset: u64,
+    pub bit_offset: u64,
+    // Строка, которую продолжит первая строка нового словаря
+    pub carried: Vec<u8>,
+}
+
+impl Point {
+    fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self.offset.to_le_bytes())?;
+        writer.write_all(&self.bit_offset.to_le_bytes())?;
+        writer.write_all(&(self.carried.len() as u32).to_le_bytes())?;
+        writer.write_all(&self.carried)
+    }
+    fn read<R: Read>(reader: &mut R) -> Result<Point> {
+        let offset = read_u64(reader)?;
+        let bit_offset = read_u64(reader)?;
+        let len = read_u32(reader)?;
+        let mut carried = Vec::new();
+        reader.take(u64::from(len)).read_to_end(&mut carried)?;
+        if carried.len() != len as usize {
+            return Err(Error::Truncated);
+        }
+        Ok(Point {
+            offset,
+            bit_offset,
+            carried,
+        })
+    }
+}
+
+/// Блок из индекса блочного архива
+#[derive(Debug, Clone)]
+pub(crate) struct IndexBlock {
+    // Смещение блока в исходных данных и его исходный размер
+    pub offset: u64,
+    pub size: u64,
+    // Байты кодов блока [start, end) от начала архива
+    pub start: u64,
+    pub end: u64,
+    // Точки входа, первая — начало блока
+    pub points: Vec<Point>,
+}
+
+/// Считывает индекс блочного архива. Заголовок архива должен быть уже проверен
+pub(crate) fn read_index<R: Read + Seek>(
+    reader: &mut R,
+    header: &Header,
+) -> Result<Vec<IndexBlock>> {
+    let corrupted = Error::BadHeader("индекс блоков поврежден");
+    let footer = reader.seek(SeekFrom::End(-(FOOTER_LEN as i64)))?;
+    let index_offset = read_u64(reader)?;
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    // Между завершающим блоком и индексом хранится контрольная сумма
+    let trailer = BLOCK_HEADER_LEN + header.checksum.map_or(0, Checksum::output_len) as u64;
+    if magic != MAGIC || index_offset > footer || index_offset < trailer {
+        return Err(corrupted);
+    }
+    // Коды последнего блока заканчиваются перед завершающим блоком
+    let codes_end = index_offset - trailer;
+    reader.seek(SeekFrom::Start(index_offset))?;
+    let count = read_u32(reader)?;
+    let mut blocks: Vec<IndexBlock> = Vec::new();
+    for _ in 0..count {
+        let start = Point {
+            offset: read_u64(reader)?,
+            bit_offset: read_u64(reader)?,
+            carried: Vec::new(),
+        };
+        let mut points = vec![start];
+        for _ in 0..read_u32(reader)? {
+            points.push(Point::read(reader)?);
+        }
+        let first = &points[0];
+        let block = IndexBlock {
+            offset: first.offset,
+            size: 0,
+            start: first.bit_offset / 8,
+            end: codes_end,
+            points,
+        };
+        if !block.points[0].bit_offset.is_multiple_of(8)
+            || block.start < BLOCK_HEADER_LEN
+            || block.start > codes_end
+        {
+            return Err(corrupted);
+        }
+        if blocks.is_empty() && block.offset != 0 {
+            return Err(corrupted);
+        }
+        if let Some(prev) = blocks.last_mut() {
+            if block.offset <= prev.offset || block.start < prev.start + BLOCK_HEADER_LEN {
+                return Err(corrupted);
+            }
+            prev.size = block.offset - prev.offset;
+            prev.end = block.start - BLOCK_HEADER_LEN;
+        }
+        blocks.push(block);
+    }
+    // Размер последнего блока берется из его заголовка
+    if let Some(last) = blocks.last_mut() {
+        reader.seek(SeekFrom::Start(last.start - BLOCK_HEADER_LEN))?;
+        let (size, len) =
+            read_block_header(reader)?.ok_or(Error::BadHeader("индекс блоков поврежден"))?;
+        last.size = u64::from(size);
+        if last.start + u64::from(len) != last.end {
+            return Err(corrupted);
+        }
+    }
+    // Точки входа лежат внутри своего блока по возрастанию
+    for block in &blocks {
+        for pair in block.points.windows(2) {
+            if pair[1].offset <= pair[0].offset || pair[1].bit_offset <= pair[0].bit_offset {
+                return Err(Error::BadHeader("индекс блоков поврежден"));
+            }
+        }
+        let last = block.points.last().unwrap();
+        if last.offset >= block.offset + block.size || last.bit_offset > block.end * 8 {
+            return Err(Error::BadHeader("индекс блоков поврежден"));
+        }
+    }
+    Ok(blocks)
+}
+
+/// Проверяет количество потоков
+pub(crate) fn check_threads(threads: usize) -> Result<usize> {
+    if !(1..=MAX_THREADS).contains(&threads) {
+        return Err(Error::InvalidOption(
+            "количество потоков должно быть от 1 до 256",
+        ));
+    }
+    Ok(threads)
+}
+
+/// Пул потоков, обрабатывающих блоки. Результаты отдаются в порядке блоков.
+///
+/// Паника при обработке блока перехватывается и возвращается
+/// как `Error::WorkerFailed` вместо результата этого блока
+struct Pool<I, T> {
+    jobs: Option<Sender<(u64, I)>>,
+    // Результат блока, None — обработка завершилась паникой
+    results: Receiver<(u64, Option<T>)>,
+    workers: Vec<JoinHandle<()>>,
+    // Номер следующего отправленного блока и следующего отдаваемого результата
+    sent: u64,
+    received: u64,
+    // Результаты, готовые раньше предыдущих
+    ready: BTreeMap<u64, Option<T>>,
+}
+
+impl<I: Send + 'static, T: Send + 'static> Pool<I, T> {
+    fn new<F>(threads: usize, job: F) -> Self
+    where
+        F: Fn(I) -> T + Send + Sync + 'static,
+    {
+        let (jobs, queue) = mpsc::channel::<(u64, I)>();
+        let (done, results) = mpsc::channel();
+        let queue = Arc::new(Mutex::new(queue));
+        let job = Arc::new(job);
+        let workers = (0..threads)
+            .map(|_| {
+                let queue = Arc::clone(&queue);
+                let done = done.clone();
+                let job = Arc::clone(&job);
+                thread::spawn(move || loop {
+                    // Очередь блокируется только на время получения задачи
+                    let next = match queue.lock() {
+                        Ok(queue) => queue.recv(),
+                        Err(_) => break,
+                    };
+                    match next {
+                        Ok((number, input)) => {
+                            let result = panic::catch_unwind(AssertUnwindSafe(|| job(input)));
+                            if done.send((number, result.ok())).is_err() {
+                                break;
+                            }
+                        }
+                        // Задач больше не будет
+                        Err(_) => break,
+                    }
+                })
+            })
+            .collect();
+        Pool {
+            jobs: Some(jobs),
+            results,
+            workers,
+            sent: 0,
+            received: 0,
+            ready: BTreeMap::new(),
+        }
+    }
+    /// Количество блоков в работе
+    fn in_flight(&self) -> usize {
+        (self.sent - self.received) as usize
+    }
+    fn submit(&mut self, input: I) -> Result<()> {
+        match &self.jobs {
+            Some(jobs) if jobs.send((self.sent, input)).is_ok() => {
+                self.sent += 1;
+                Ok(())
+            }
+            // Все потоки пула завершились
+            _ => Err(Error::WorkerFailed),
+        }
+    }
+    /// Результат следующего по порядку блока. Если он еще не готов,
+    /// дожидается его при wait, иначе возвращает None
+    fn next(&mut self, wait: bool) -> Option<Result<T>> {
+        loop {
+            if let Some(result) = self.ready.remove(&self.received) {
+                self.received += 1;
+                return Some(result.ok_or(Error::WorkerFailed));
+            }
+            if self.received == self.sent {
+                return None;
+            }
+            let message = if wait {
+                match self.results.recv() {
+                    Ok(message) => Some(message),
+                    Err(_) => return Some(Err(Error::WorkerFailed)),
+                }
+            } else {
+                self.results.try_recv().ok()
+            };
+            let (number, result) = message?;
+            self.ready.insert(number, result);
+        }
+    }
+}
+
+impl<I, T> Drop for Pool<I, T> {
+    fn drop(&mut self) {
+        // Закрываем очередь, потоки завершатся после текущих задач
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Сколько блоков может находиться в работе на один поток. Ограничивает
+/// память, если данные поступают быстрее, чем сжимаются или записываются
+const BLOCKS_PER_THREAD: usize = 2;
+
+/// Кодировщик блочного архива, реализующий `Write`. Заголовок архива
+/// записывается при создании, индекс блоков — в `finish`
+pub(crate) struct BlockEncoder<W: Write> {
+    writer: BufWriter<W>,
+    pool: Pool<Vec<u8>, (u32, io::Result<Compressed>)>,
+    // Блок, который еще набирается
+    block: Vec<u8>,
+    block_size: usize,
+    // Наибольшее количество блоков в работе
+    limit: usize,
+    // Точки входа каждого блока, первая — начало блока
+    index: Vec<Vec<Point>>,
+    // Исходный размер записанных блоков и размер архива
+    in_offset: u64,
+    out_offset: u64,
+    // Контрольная сумма исходных данных, если она хранится в архиве
+    hasher: Option<Hasher>,
+}
+
+impl<W: Write> BlockEncoder<W> {
+    /// Создает кодировщик и записывает заголовок архива, дополненный `FLAG_BLOCKS`
+    pub fn new(writer: W, header: Header, block_size: usize, threads: usize) -> Result<Self> {
+        check_threads(threads)?;
+        if !(1..=MAX_BLOCK_SIZE).contains(&block_size) {
+            return Err(Error::InvalidOption(
+                "размер блока должен быть от 1 байта до 256 МиБ",
+            ));
+        }
+        // Размер словаря проверяется до запуска потоков
+        Compress::new(&header)?;
+        let header = Header {
+            flags: header.flags | FLAG_BLOCKS,
+            ..header
+        };
+        let mut writer = BufWriter::new(writer);
+        let mut buf = Vec::new();
+        header.write(&mut buf)?;
+        writer.write_all(&buf)?;
+        let pool = Pool::new(threads, move |block: Vec<u8>| {
+            (block.len() as u32, compress_block(&block, &header))
+        });
+        Ok(BlockEncoder {
+            writer,
+            pool,
+            block: Vec::with_capacity(block_size),
+            block_size,
+            limit: threads * BLOCKS_PER_THREAD,
+            index: Vec::new(),
+            in_offset: 0,
+            out_offset: buf.len() as u64,
+            hasher: header.checksum.map(Hasher::new),
+        })
+    }
+    /// Дожимает последний блок, записывает индекс и концевик,
+    /// возвращает вложенный поток
+    pub fn finish(mut self) -> Result<W> {
+        if !self.block.is_empty() {
+            let block = mem::take(&mut self.block);
+            self.pool.submit(block)?;
+        }
+        self.write_ready(0)?;
+        // Блок нулевого размера
+        self.writer.write_all(&[0; BLOCK_HEADER_LEN as usize])?;
+        let mut index_offset = self.out_offset + BLOCK_HEADER_LEN;
+        if let Some(hasher) = self.hasher.take() {
+            let checksum = hasher.finish();
+            self.writer.write_all(&checksum)?;
+            index_offset += checksum.len() as u64;
+        }
+        self.writer
+            .write_all(&(self.index.len() as u32).to_le_bytes())?;
+        for points in &self.index {
+            let (start, points) = points.split_first().expect("Блок без начала");
+            self.writer.write_all(&start.offset.to_le_bytes())?;
+            self.writer.write_all(&start.bit_offset.to_le_bytes())?;
+            self.writer
+                .write_all(&(points.len() as u32).to_le_bytes())?;
+            for point in points {
+                point.write(&mut self.writer)?;
+            }
+        }
+        self.writer.write_all(&index_offset.to_le_bytes())?;
+        self.writer.write_all(&MAGIC)?;
+        Ok(self.writer.into_inner().map_err(|err| err.into_error())?)
+    }
+    /// Записывает готовые блоки по порядку, дожидаясь их,
+    /// пока в работе больше max блоков
+    fn write_ready(&mut self, max: usize) -> io::Result<()> {
+        loop {
+            let wait = self.pool.in_flight() > max;
+            match self.pool.next(wait) {
+                Some(result) => {
+                    let (size, block) = result?;
+                    let (codes, points) = block?;
+                    self.write_block(size, &codes, points)?
+                }
+                None => return Ok(()),
+            }
+        }
+    }
+    /// Записывает сжатый блок. Точки входа переводятся в смещения от начала архива
+    fn write_block(&mut self, size: u32, codes: &[u8], points: Vec<Point>) -> io::Result<()> {
+        self.writer.write_all(&size.to_le_bytes())?;
+        self.writer.write_all(&(codes.len() as u32).to_le_bytes())?;
+        self.out_offset += BLOCK_HEADER_LEN;
+        let start = Point {
+            offset: 0,
+            bit_offset: 0,
+            carried: Vec::new(),
+        };
+        let (in_offset, bit_offset) = (self.in_offset, self.out_offset * 8);
+        self.index.push(
+            Some(start)
+                .into_iter()
+                .chain(points)
+                .map(|point| Point {
+                    offset: in_offset + point.offset,
+                    bit_offset: bit_offset + point.bit_offset,
+                    carried: point.carried,
+                })
+                .collect(),
+        );
+        self.writer.write_all(codes)?;
+        self.in_offset += u64::from(size);
+        self.out_offset += codes.len() as u64;
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for BlockEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.block_size - self.block.len());
+        self.block.extend_from_slice(&buf[..len]);
+        if let Some(hasher) = self.hasher.as_mut() {
+            hasher.update(&buf[..len]);
+        }
+        if self.block.len() == self.block_size {
+            let block = mem::replace(&mut self.block, Vec::with_capacity(self.block_size));
+            self.pool.submit(block)?;
+            self.write_ready(self.limit - 1)?;
+        }
+        Ok(len)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Коды сжатого блока и точки входа внутри него
+type Compressed = (Vec<u8>, Vec<Point>);
+
+/// Сжимает блок отдельным словарем. Возвращает коды и точки входа внутри блока
+fn compress_block(block: &[u8], header: &Header) -> io::Result<Compressed> {
+    let mut codes = Vec::new();
+    let mut lzw_struct = Compress::new(header)?;
+    // Энтропийный кодер нельзя начать с середины его потока
+    if header.entropy.is_none() {
+        lzw_struct.points = Some(Vec::new());
+    }
+    lzw_struct.compress(block, &mut codes)?;
+    lzw_struct.last_bytes(&mut codes)?;
+    Ok((codes, lzw_struct.points.take().unwrap_or_default()))
+}
+/// Распаковывает блок и проверяет его исходный размер
+fn decompress_block(codes: &[u8], size: u32, header: &Header) -> Result<Vec<u8>> {
+    let mut block = BlockBuffer {
+        data: Vec::new(),
+        size: size as usize,
+    };
+    Decompress::new(header)?.decompress(codes, &mut block)?;
+    if block.data.len() != block.size {
+        return Err(Error::BadHeader("неверный размер блока"));
+    }
+    Ok(block.data)
+}
+
+/// Распакованный блок. Размер из заголовка не заверен, поэтому память
+/// выделяется по мере поступления данных, а данные сверх него сразу
+/// считаются ошибкой
+struct BlockBuffer {
+    data: Vec<u8>,
+    size: usize,
+}
+impl Write for BlockBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.len() > self.size - self.data.len() {
+            return Err(Error::BadHeader("неверный размер блока").into());
+        }
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Считывает заголовок блока: исходный размер и размер кодов.
+/// Возвращает None для завершающего блока. Исходный размер больше
+/// `MAX_BLOCK_SIZE` означает поврежденный архив: такой блок не мог
+/// быть записан
+pub(crate) fn read_block_header<R: Read>(reader: &mut R) -> Result<Option<(u32, u32)>> {
+    let mut buf = [0u8; BLOCK_HEADER_LEN as usize];
+    reader.read_exact(&mut buf)?;
+    let mut size = [0u8; 4];
+    size.copy_from_slice(&buf[..4]);
+    let mut len = [0u8; 4];
+    len.copy_from_slice(&buf[4..]);
+    match (u32::from_le_bytes(size), u32::from_le_bytes(len)) {
+        (0, 0) => Ok(None),
+        (0, _) => Err(Error::BadHeader("неверный размер блока")),
+        (size, _) if size as usize > MAX_BLOCK_SIZE => {
+            Err(Error::BadHeader("неверный размер блока"))
+        }
+        block => Ok(Some(block)),
+    }
+}
+
+/// Распаковывает блоки из reader, установленного сразу за заголовком архива,
+/// в threads потоков. Индекс после блоков не считывается
+pub(crate) fn decompress_blocks<R: Read, W: Write>(
+    mut reader: R,
+    writer: &mut W,
+    header: &Header,
+    threads: usize,
+) -> Result<()> {
+    check_threads(threads)?;
+    // Размер словаря проверяется до запуска потоков
+    Decompress::new(header)?;
+    let shared = *header;
+    let mut pool = Pool::new(threads, move |(size, codes): (u32, Vec<u8>)| {
+        decompress_block(&codes, size, &shared)
+    });
+    let limit = threads * BLOCKS_PER_THREAD;
+    let mut hasher = header.checksum.map(Hasher::new);
+    let mut write = |block: Vec<u8>| -> Result<()> {
+        if let Some(hasher) = hasher.as_mut() {
+            hasher.update(&block);
+        }
+        writer.write_all(&block)?;
+        Ok(())
+    };
+    while let Some((size, len)) = read_block_header(&mut reader)? {
+        let mut codes = Vec::new();
+        (&mut reader).take(u64::from(len)).read_to_end(&mut codes)?;
+        if codes.len() != len as usize {
+            return Err(Error::Truncated);
+        }
+        pool.submit((size, codes))?;
+        while let Some(block) = pool.next(pool.in_flight() >= limit) {
+            write(block??)?;
+        }
+    }
+    while let Some(block) = pool.next(true) {
+        write(block??)?;
+    }
+    // Контрольная сумма следует за завершающим блоком
+    if let (Some(hasher), Some(checksum)) = (hasher, header.checksum) {
+        let mut expected = vec![0u8; checksum.output_len()];
+        reader.read_exact(&mut expected)?;
+        hasher.verify(&expected)?;
+    }
+    Ok(())
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
@@ -0,0 +1,178 @@
+//! Контрольная сумма исходных данных
+//!
+//! Если в заголовке установлен `FLAG_CHECKSUM`, за кодами следует
+//! контрольная сумма всех исходных данных выбранным алгоритмом. В обычном
+//! архиве она занимает последние байты потока (в зашифрованном — последние
+//! байты перед дополнением AES), в блочном записывается сразу после
+//! завершающего блока. Распаковка сверяет ее с суммой распакованных данных.
+use super::error::{Error, Result};
+use ring::digest;
+use std::hash::Hasher as _;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use twox_hash::XxHash64;
+
+/// Алгоритм контрольной суммы
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checksum {
+    /// CRC32 (IEEE), 4 байта
+    Crc32,
+    /// xxHash64 с нулевым начальным значением, 8 байт. Быстрее CRC32
+    XxHash64,
+    /// SHA-256, 32 байта. Самый медленный, зато защищает и от намеренной подмены
+    Sha256,
+}
+
+impl Checksum {
+    /// Идентификатор алгоритма в заголовке архива
+    pub fn id(self) -> u8 {
+        match self {
+            Checksum::Crc32 => 1,
+            Checksum::XxHash64 => 2,
+            Checksum::Sha256 => 3,
+        }
+    }
+    /// Алгоритм по идентификатору из заголовка
+    pub fn from_id(id: u8) -> Option<Checksum> {
+        match id {
+            1 => Some(Checksum::Crc32),
+            2 => Some(Checksum::XxHash64),
+            3 => Some(Checksum::Sha256),
+            _ => None,
+        }
+    }
+    /// Длина контрольной суммы в байтах
+    pub fn output_len(self) -> usize {
+        match self {
+            Checksum::Crc32 => 4,
+            Checksum::XxHash64 => 8,
+            Checksum::Sha256 => digest::SHA256.output_len,
+        }
+    }
+}
+
+/// Вычисление контрольной суммы
+#[derive(Clone)]
+pub(crate) enum Hasher {
+    Crc32(crc32fast::Hasher),
+    XxHash64(XxHash64),
+    Sha256(digest::Context),
+}
+
+impl Hasher {
+    pub fn new(checksum: Checksum) -> Self {
+        match checksum {
+            Checksum::Crc32 => Hasher::Crc32(crc32fast::Hasher::new()),
+            Checksum::XxHash64 => Hasher::XxHash64(XxHash64::with_seed(0)),
+            Checksum::Sha256 => Hasher::Sha256(digest::Context::new(&digest::SHA256)),
+        }
+    }
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Crc32(hasher) => hasher.update(data),
+            Hasher::XxHash64(hasher) => hasher.write(data),
+            Hasher::Sha256(context) => context.update(data),
+        }
+    }
+    /// Контрольная сумма в том виде, в котором она хранится в архиве
+    pub fn finish(self) -> Vec<u8> {
+        match self {
+            Hasher::Crc32(hasher) => hasher.finalize().to_le_bytes().to_vec(),
+            Hasher::XxHash64(hasher) => hasher.finish().to_le_bytes().to_vec(),
+            Hasher::Sha256(context) => context.finish().as_ref().to_vec(),
+        }
+    }
+    /// Сверяет контрольную сумму с сохраненной в архиве
+    pub fn verify(self, expected: &[u8]) -> Result<()> {
+        if self.finish() != expected {
+            return Err(Error::ChecksumMismatch);
+        }
+        Ok(())
+    }
+}
+
+/// Поток, вычисляющий контрольную сумму всех записываемых в него данных,
+/// если алгоритм задан
+pub(crate) struct HashWriter<'a, W: Write> {
+    pub writer: &'a mut W,
+    pub hasher: Option<Hasher>,
+}
+
+impl<'a, W: Write> Write for HashWriter<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.writer.write(buf)?;
+        if let Some(hasher) = self.hasher.as_mut() {
+            hasher.update(&buf[..len]);
+        }
+        Ok(len)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Поток, который не отдает последние len байт вложенного потока:
+/// в них хранится контрольная сумма. Читает порциями, поэтому побайтное
+/// чтение лучше буферизовать поверх него
+pub(crate) struct Trailer<R: Read> {
+    reader: R,
+    len: usize,
+    // Считанные, но еще не отданные байты, не больше len после каждого чтения
+    held: Vec<u8>,
+}
+
+impl<R: Read> Trailer<R> {
+    pub fn new(reader: R, len: usize) -> Self {
+        Trailer {
+            reader,
+            len,
+            held: Vec::new(),
+        }
+    }
+    /// Ссылка на вложенный поток
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+    /// Последние len байт потока. Вызывается после того, как чтение вернуло 0
+    pub fn trailer(&self) -> Result<&[u8]> {
+        if self.held.len() != self.len {
+            return Err(Error::Truncated);
+        }
+        Ok(&self.held)
+    }
+}
+
+impl<R: Read> Read for Trailer<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.len == 0 || buf.is_empty() {
+            return self.reader.read(buf);
+        }
+        loop {
+            let start = self.held.len();
+            self.held.resize(start + buf.len(), 0);
+            let bytes_read = self.reader.read(&mut self.held[start..])?;
+            self.held.truncate(start + bytes_read);
+            if bytes_read == 0 {
+                return Ok(0);
+            }
+            // Отдаем все, кроме последних len байт
+            if self.held.len() > self.len {
+                let len = self.held.len() - self.len;
+                buf[..len].copy_from_slice(&self.held[..len]);
+                self.held.drain(..len);
+                return Ok(len);
+            }
+        }
+    }
+}
+
+impl<R: Read + Seek> Seek for Trailer<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        // Удержанные байты еще не отданы, т.е. позиция отстает на их количество
+        let pos = match pos {
+            SeekFrom::Current(offset) => SeekFrom::Current(offset - self.held.len() as i64),
+            pos => pos,
+        };
+        self.held.clear();
+        self.reader.seek(pos)
+    }
+}
@@ -0,0 +1,727 @@
+//! Общее ядро LZW
+//!
+//! Словарь (`Table`) — поиск строк, их восстановление по коду, случай
+//! KwKwK и сбросы — один для основного формата и форматов `.Z`, GIF,
+//! TIFF/PDF и Shrink. Последние различаются только параметрами (`Params`):
+//! количеством литералов, управляющими кодами и разрядностью кодов;
+//! кодировщик и декодировщик для них реализованы здесь один раз, а формат
+//! задает параметры и упаковку кодов в байты (`CodeSink`, `CodeSource`).
+use super::bits::{BitOrder, BitReader, BitWriter};
+use super::error::{Error, Result};
+use std::collections::HashMap;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+/// Что делает кодировщик, когда словарь заполнен
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Full {
+    /// Выдает код сброса и начинает словарь заново
+    Clear,
+    /// Перестает добавлять строки; о сбросе решает формат
+    Keep,
+    /// Частичный сброс: освобождает строки, которые не служат префиксом
+    /// других, и выдает их коды повторно
+    PartialClear,
+}
+
+/// Подкод управляющего кода: увеличение разрядности на единицу
+const INC_CODE_SIZE: u32 = 1;
+/// Подкод управляющего кода: частичный сброс словаря
+const PARTIAL_CLEAR: u32 = 2;
+
+/// Параметры варианта LZW
+#[derive(Debug, Clone, Copy)]
+pub struct Params {
+    /// Количество литералов: коды 0..literals означают сами символы
+    pub literals: u32,
+    /// Код сброса словаря, если формат его поддерживает
+    pub clear: Option<u32>,
+    /// Код конца данных, если он есть
+    pub end: Option<u32>,
+    /// Поток обязан заканчиваться кодом конца данных
+    pub end_required: bool,
+    /// Управляющий код, за которым следует подкод той же разрядности.
+    /// С ним разрядность растет только явно, подкодом `INC_CODE_SIZE`
+    pub control: Option<u32>,
+    /// Начальная разрядность кодов
+    pub init_bits: u8,
+    /// Наибольшая разрядность кодов
+    pub max_bits: u8,
+    /// Размер словаря вместе с литералами и управляющими кодами
+    pub table_size: u32,
+    /// Сколько последних кодов словаря кодировщик оставляет свободными
+    pub spare: u32,
+    /// Ранний переход к следующей разрядности: на один код раньше,
+    /// чем это строго необходимо
+    pub early_change: bool,
+    /// Поведение кодировщика при заполненном словаре
+    pub full: Full,
+}
+
+impl Params {
+    /// Первый код, который получает новая строка
+    fn first(&self) -> u32 {
+        match self.end.or(self.clear).or(self.control) {
+            Some(code) => code + 1,
+            None => self.literals,
+        }
+    }
+    /// Нужно ли перейти к следующей разрядности, когда следующий
+    /// свободный код равен next
+    fn widens(&self, bits: u8, next: u32) -> bool {
+        next + self.early_change as u32 >= 1 << bits && bits < self.max_bits
+    }
+    /// Пустой словарь из size кодов
+    fn table(&self, size: u32, search: bool) -> Table {
+        let mut table = Table::new(self.literals, size, search);
+        table.reserve(size as usize);
+        table.reset(self.first());
+        table
+    }
+}
+
+/// Запись кодов в поток байт
+pub trait CodeSink {
+    /// Записывает код разрядности bits
+    fn write_code(&mut self, code: u32, bits: u8) -> io::Result<()>;
+    /// Вызывается перед сменой разрядности и после сброса словаря
+    fn change_width(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+    /// Сбрасывает буферизованные байты во вложенный поток
+    fn flush(&mut self) -> io::Result<()>;
+}
+
+/// Коды, записанные подряд без выравнивания
+pub struct BitSink<W: Write> {
+    writer: BufWriter<W>,
+    bits: BitWriter,
+}
+
+impl<W: Write> BitSink<W> {
+    pub fn new(writer: W, order: BitOrder) -> Self {
+        BitSink {
+            writer: BufWriter::new(writer),
+            bits: BitWriter::new(order),
+        }
+    }
+    /// Записывает неполный байт, дополняя его нулями, и возвращает вложенный поток
+    pub fn finish(mut self) -> Result<W> {
+        self.bits.pad(&mut self.writer)?;
+        Ok(self.writer.into_inner().map_err(|err| err.into_error())?)
+    }
+}
+
+impl<W: Write> CodeSink for BitSink<W> {
+    fn write_code(&mut self, code: u32, bits: u8) -> io::Result<()> {
+        self.bits.write_bits(&mut self.writer, code, bits)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Чтение кодов из потока байт
+pub trait CodeSource {
+    /// Считывает код разрядности bits или None в конце потока
+    fn read_code(&mut self, bits: u8) -> io::Result<Option<u32>>;
+    /// Вызывается после смены разрядности и после сброса словаря
+    fn change_width(&mut self) {}
+}
+
+/// Коды, считанные подряд без выравнивания
+pub struct BitSource<R: Read> {
+    reader: BufReader<R>,
+    bits: BitReader,
+}
+
+impl<R: Read> BitSource<R> {
+    pub fn new(reader: R, order: BitOrder) -> Self {
+        BitSource {
+            reader: BufReader::new(reader),
+            bits: BitReader::new(order),
+        }
+    }
+    /// Возвращает вложенный поток. Данные, уже считанные в буфер, теряются
+    pub fn into_inner(self) -> R {
+        self.reader.into_inner()
+    }
+}
+
+impl<R: Read> CodeSource for BitSource<R> {
+    fn read_code(&mut self, bits: u8) -> io::Result<Option<u32>> {
+        self.bits.read_bits(&mut self.reader, bits)
+    }
+}
+
+/// Префикс свободного кода
+const FREE: u32 = u32::MAX;
+
+/// Прошлое кодовое слово декодировщика, которое продолжит новая строка словаря
+#[derive(Debug, Clone, Copy)]
+pub enum Prev<'a> {
+    /// Код строки из словаря
+    Code(u32),
+    /// Строка целиком: кодовое слово из сброшенного словаря
+    Carried(&'a [u8]),
+}
+
+/// Словарь: каждая строка хранится кодом префикса и последним символом.
+/// Новые строки получают коды по возрастанию, а после частичного сброса —
+/// освобожденные им коды. Словарь общий для основного формата и форматов
+/// `.Z`, GIF, TIFF/PDF и Shrink
+pub struct Table {
+    literals: u32,
+    // Первый код новых строк и наибольшее количество кодов
+    first: u32,
+    size: u32,
+    // Код префикса, последний символ и длина каждой строки. У литералов,
+    // зарезервированных и свободных кодов префикс FREE, у двух последних
+    // длина 0
+    prefix: Vec<u32>,
+    suffix: Vec<u8>,
+    length: Vec<u32>,
+    // Поиск строки по коду префикса и следующему символу. Нужен только кодировщику
+    search: Option<HashMap<(u32, u8), u32>>,
+    // Строка, префикса которой еще нет в словаре, и ее код. Хранится целиком,
+    // пока префикс не будет добавлен
+    detached: Option<(Vec<u8>, u32)>,
+    // Коды, освобожденные частичным сбросом, по возрастанию, и позиция следующего из них
+    free: Vec<u32>,
+    next_free: usize,
+}
+
+impl Table {
+    /// Создает словарь не больше чем из size кодов. search — нужен ли поиск строк
+    pub fn new(literals: u32, size: u32, search: bool) -> Self {
+        let mut table = Table {
+            literals,
+            first: literals,
+            size,
+            prefix: Vec::new(),
+            suffix: Vec::new(),
+            length: Vec::new(),
+            search: if search { Some(HashMap::new()) } else { None },
+            detached: None,
+            free: Vec::new(),
+            next_free: 0,
+        };
+        table.reset(literals);
+        table
+    }
+    /// Выделяет память еще под additional строк
+    pub fn reserve(&mut self, additional: usize) {
+        self.prefix.reserve(additional);
+        self.suffix.reserve(additional);
+        self.length.reserve(additional);
+        if let Some(search) = self.search.as_mut() {
+            search.reserve(additional);
+        }
+    }
+    /// Освобождает все строки. Коды от литералов до first зарезервированы
+    pub fn reset(&mut self, first: u32) {
+        self.first = first;
+        self.prefix.clear();
+        self.suffix.clear();
+        self.length.clear();
+        self.prefix.resize(first as usize, FREE);
+        self.suffix.extend((0..first).map(|code| code as u8));
+        let literals = self.literals;
+        self.length
+            .extend((0..first).map(|code| (code < literals) as u32));
+        if let Some(search) = self.search.as_mut() {
+            search.clear();
+        }
+        self.detached = None;
+        self.free.clear();
+        self.next_free = 0;
+    }
+    /// Количество занятых и зарезервированных кодов
+    pub fn len(&self) -> u32 {
+        self.prefix.len() as u32
+    }
+    /// Код, который получит следующая строка, или None, если словарь заполнен
+    pub fn peek_free(&self) -> Option<u32> {
+        match self.free.get(self.next_free) {
+            Some(&code) => Some(code),
+            None if self.len() < self.size => Some(self.len()),
+            None => None,
+        }
+    }
+    /// Следующий свободный код или размер словаря, если тот заполнен
+    pub fn next_code(&self) -> u32 {
+        self.peek_free().unwrap_or(self.size)
+    }
+    /// Код строки prefix + symbol
+    #[inline]
+    pub fn find(&self, prefix: u32, symbol: u8) -> Option<u32> {
+        self.search.as_ref()?.get(&(prefix, symbol)).cloned()
+    }
+    /// Добавляет строку prefix + symbol под кодом, который вернул `peek_free`
+    pub fn insert(&mut self, prefix: u32, symbol: u8) -> Option<u32> {
+        let code = self.peek_free()?;
+        self.replace(code, prefix, symbol);
+        Some(code)
+    }
+    /// Записывает строку prefix + symbol под кодом code: следующим
+    /// или кодом вытесняемой строки
+    pub fn replace(&mut self, code: u32, prefix: u32, symbol: u8) {
+        let position = self.place(code);
+        if let Some(search) = self.search.as_mut() {
+            let old = (self.prefix[position], self.suffix[position]);
+            if old.0 != FREE && search.get(&old) == Some(&code) {
+                search.remove(&old);
+            }
+            search.insert((prefix, symbol), code);
+        }
+        self.prefix[position] = prefix;
+        self.suffix[position] = symbol;
+        self.length[position] = self.length[prefix as usize] + 1;
+        // Отложенная строка становится доступной вместе со своим префиксом
+        let linked = match &self.detached {
+            Some((string, _)) => self.is_string(code, &string[..string.len() - 1]),
+            None => false,
+        };
+        if linked {
+            let (string, detached) = self.detached.take().unwrap();
+            self.replace(detached, code, string[string.len() - 1]);
+        }
+    }
+    /// Занимает следующий код строкой, которая никогда не будет найдена
+    pub fn skip(&mut self) {
+        self.prefix.push(FREE);
+        self.suffix.push(0);
+        self.length.push(0);
+    }
+    /// Добавляет строку под следующим кодом. Ее префикса может еще не быть
+    /// в словаре: тогда строка хранится целиком и станет доступна для поиска,
+    /// когда префикс будет добавлен
+    pub fn insert_detached(&mut self, string: Vec<u8>) {
+        let (&symbol, head) = string.split_last().expect("Пустая отложенная строка");
+        let mut prefix = Some(u32::from(head[0]));
+        for &ch in &head[1..] {
+            prefix = prefix.and_then(|prefix| self.find(prefix, ch));
+        }
+        match prefix {
+            Some(prefix) => {
+                self.insert(prefix, symbol);
+            }
+            None => {
+                let code = self.len();
+                self.skip();
+                self.length[code as usize] = string.len() as u32;
+                self.detached = Some((string, code));
+            }
+        }
+    }
+    /// Позиция кода code, который становится занятым: следующего
+    /// после последнего, освобожденного или уже занятого
+    fn place(&mut self, code: u32) -> usize {
+        if self.free.get(self.next_free) == Some(&code) {
+            self.next_free += 1;
+        }
+        let position = code as usize;
+        if position == self.prefix.len() {
+            self.prefix.push(FREE);
+            self.suffix.push(0);
+            self.length.push(0);
+        }
+        position
+    }
+    /// Дописывает строку кода code в out, проходя по префиксам с конца
+    pub fn write_string(&self, code: u32, out: &mut Vec<u8>) -> Result<()> {
+        let length = match self.length.get(code as usize) {
+            Some(&length) if length > 0 => length as usize,
+            _ => return Err(Error::InvalidCode(code)),
+        };
+        let start = out.len();
+        out.resize(start + length, 0);
+        let string = &mut out[start..];
+        let mut end = length;
+        let mut current = code;
+        // У литерала префикса нет, поэтому цикл останавливается на нем
+        while end > 1 {
+            let position = current as usize;
+            let prefix = self.prefix[position];
+            if prefix == FREE {
+                match &self.detached {
+                    Some((detached, code)) if *code == current && detached.len() == end => {
+                        string[..end].copy_from_slice(detached);
+                        return Ok(());
+                    }
+                    _ => break,
+                }
+            }
+            end -= 1;
+            string[end] = self.suffix[position];
+            current = prefix;
+        }
+        if current < self.literals && end == 1 {
+            string[0] = current as u8;
+            return Ok(());
+        }
+        // Длина устаревает, когда частичный сброс освобождает префикс строки
+        // и его код получает другая строка
+        out.truncate(start);
+        self.walk_string(code, out)
+    }
+    /// Дописывает строку кода code в out, не полагаясь на длины строк
+    fn walk_string(&self, code: u32, out: &mut Vec<u8>) -> Result<()> {
+        let start = out.len();
+        let mut current = code;
+        while current >= self.literals {
+            let position = current as usize;
+            match self.prefix.get(position) {
+                Some(&FREE) => match &self.detached {
+                    Some((string, detached)) if *detached == current => {
+                        out.extend(string.iter().rev());
+                        break;
+                    }
+                    _ => return Err(Error::InvalidCode(code)),
+                },
+                // Зацикленная цепочка префиксов возможна только в поврежденном потоке
+                Some(&prefix) if out.len() - start < self.prefix.len() => {
+                    out.push(self.suffix[position]);
+                    current = prefix;
+                }
+                _ => return Err(Error::InvalidCode(code)),
+            }
+        }
+        if current < self.literals {
+            out.push(current as u8);
+        }
+        out[start..].reverse();
+        Ok(())
+    }
+    /// Восстанавливает строку по коду
+    pub fn string(&self, code: u32) -> Vec<u8> {
+        let mut string = Vec::new();
+        self.write_string(code, &mut string)
+            .expect("Строки нет в словаре");
+        string
+    }
+    /// Строка с заданным кодом совпадает с string?
+    fn is_string(&self, mut code: u32, string: &[u8]) -> bool {
+        for &ch in string[1..].iter().rev() {
+            match self.prefix.get(code as usize) {
+                Some(&prefix) if prefix != FREE && self.suffix[code as usize] == ch => {
+                    code = prefix
+                }
+                _ => return false,
+            }
+        }
+        code == u32::from(string[0])
+    }
+    /// Первый символ строки
+    fn first_symbol(&self, prev: Prev) -> Option<u8> {
+        let mut current = match prev {
+            Prev::Code(code) => code,
+            Prev::Carried(string) => return string.first().cloned(),
+        };
+        for _ in 0..self.prefix.len() {
+            if current < self.literals {
+                return Some(current as u8);
+            }
+            current = match self.prefix.get(current as usize) {
+                Some(&FREE) => match &self.detached {
+                    Some((string, detached)) if *detached == current => return Some(string[0]),
+                    _ => return None,
+                },
+                Some(&prefix) => prefix,
+                None => return None,
+            };
+        }
+        None
+    }
+    /// Декодирует код: дописывает его строку в out и добавляет в словарь
+    /// строку target: прошлое кодовое слово + первый символ текущего.
+    /// Код добавляемой строки может прийти раньше, чем она добавлена (KwKwK):
+    /// тогда кодовое слово — это прошлое + его первый символ
+    pub fn decode(
+        &mut self,
+        code: u32,
+        target: Option<(u32, Prev)>,
+        out: &mut Vec<u8>,
+    ) -> Result<()> {
+        let start = out.len();
+        match target {
+            Some((target, prev)) if target == code => {
+                let first = self.first_symbol(prev).ok_or(Error::InvalidCode(code))?;
+                self.add(target, prev, first);
+                self.write_string(code, out)
+            }
+            _ => {
+                self.write_string(code, out)?;
+                if let Some((target, prev)) = target {
+                    self.add(target, prev, out[start]);
+                }
+                Ok(())
+            }
+        }
+    }
+    /// Добавляет в словарь под кодом code строку prev + symbol
+    fn add(&mut self, code: u32, prev: Prev, symbol: u8) {
+        match prev {
+            Prev::Code(prefix) => self.replace(code, prefix, symbol),
+            Prev::Carried(string) => {
+                let position = self.place(code);
+                self.prefix[position] = FREE;
+                self.length[position] = string.len() as u32 + 1;
+                let mut string = string.to_vec();
+                string.push(symbol);
+                self.detached = Some((string, code));
+            }
+        }
+    }
+    /// Освобождает строки, которые не служат префиксом ни одной другой.
+    ///
+    /// Свободный код, к которому после сброса успели добавить продолжение,
+    /// остается свободным, как в unzip от Info-ZIP
+    pub fn partial_clear(&mut self) {
+        let len = self.prefix.len();
+        let mut is_prefix = vec![false; len];
+        for &prefix in self.prefix.iter().filter(|&&prefix| prefix != FREE) {
+            is_prefix[prefix as usize] = true;
+        }
+        self.free.clear();
+        self.next_free = 0;
+        let first = self.first as usize;
+        for (code, &is_prefix) in is_prefix.iter().enumerate().skip(first) {
+            let prefix = self.prefix[code];
+            if !is_prefix && prefix != FREE {
+                if let Some(search) = self.search.as_mut() {
+                    search.remove(&(prefix, self.suffix[code]));
+                }
+                self.prefix[code] = FREE;
+                self.length[code] = 0;
+            }
+            if self.prefix[code] == FREE {
+                self.free.push(code as u32);
+            }
+        }
+    }
+}
+
+/// Кодировщик: ищет самую длинную строку из словаря и передает ее код в sink
+pub struct Encoder<S: CodeSink> {
+    params: Params,
+    sink: S,
+    table: Table,
+    bits: u8,
+    // Код текущей строки, None до первого символа
+    ent: Option<u32>,
+}
+
+impl<S: CodeSink> Encoder<S> {
+    pub fn new(params: Params, sink: S) -> Self {
+        Encoder {
+            params,
+            sink,
+            table: params.table(params.table_size - params.spare, true),
+            bits: params.init_bits,
+            ent: None,
+        }
+    }
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+    /// Добавляет символ. Возвращает true, если строка закончилась,
+    /// а словарь заполнен и новая строка в него не добавлена
+    pub fn push(&mut self, symbol: u8) -> io::Result<bool> {
+        if u32::from(symbol) >= self.params.literals {
+            return Err(Error::InvalidSymbol(symbol).into());
+        }
+        let ent = match self.ent {
+            Some(ent) => ent,
+            None => {
+                self.ent = Some(u32::from(symbol));
+                return Ok(false);
+            }
+        };
+        if let Some(code) = self.table.find(ent, symbol) {
+            self.ent = Some(code);
+            return Ok(false);
+        }
+        self.output(ent)?;
+        self.ent = Some(u32::from(symbol));
+        if self.table.peek_free().is_none() {
+            match self.params.full {
+                Full::Clear => {
+                    self.clear()?;
+                    return Ok(false);
+                }
+                Full::Keep => return Ok(true),
+                Full::PartialClear => self.partial_clear()?,
+            }
+        }
+        self.table.insert(ent, symbol);
+        Ok(false)
+    }
+    /// Записывает код сброса и начинает словарь заново
+    pub fn clear(&mut self) -> io::Result<()> {
+        if let Some(clear) = self.params.clear {
+            self.sink.write_code(clear, self.bits)?;
+        }
+        self.table.reset(self.params.first());
+        self.sink.change_width()?;
+        self.bits = self.params.init_bits;
+        Ok(())
+    }
+    /// Освобождает строки, которые не служат префиксом других,
+    /// и записывает управляющий код частичного сброса
+    fn partial_clear(&mut self) -> io::Result<()> {
+        self.table.partial_clear();
+        if let Some(control) = self.params.control {
+            self.sink.write_code(control, self.bits)?;
+            self.sink.write_code(PARTIAL_CLEAR, self.bits)?;
+        }
+        Ok(())
+    }
+    /// Записывает код последней строки и код конца данных, возвращает sink
+    pub fn finish(mut self) -> io::Result<S> {
+        if let Some(ent) = self.ent {
+            self.output(ent)?;
+        }
+        if let Some(end) = self.params.end {
+            self.sink.write_code(end, self.bits)?;
+        }
+        Ok(self.sink)
+    }
+    /// Записывает код текущей разрядности. Разрядность растет, как только
+    /// следующий свободный код в нее не помещается, а при управляющем
+    /// коде — перед кодом, который в нее не помещается
+    fn output(&mut self, code: u32) -> io::Result<()> {
+        if let Some(control) = self.params.control {
+            while code >= 1 << self.bits {
+                self.sink.write_code(control, self.bits)?;
+                self.sink.write_code(INC_CODE_SIZE, self.bits)?;
+                self.bits += 1;
+            }
+            return self.sink.write_code(code, self.bits);
+        }
+        self.sink.write_code(code, self.bits)?;
+        if self.params.widens(self.bits, self.table.next_code()) {
+            self.sink.change_width()?;
+            self.bits += 1;
+        }
+        Ok(())
+    }
+}
+
+impl<S: CodeSink> Write for Encoder<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &symbol in buf {
+            self.push(symbol)?;
+        }
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.sink.flush()
+    }
+}
+
+/// Декодировщик, реализующий `Read`: читает коды из source и отдает строки
+pub struct Decoder<S: CodeSource> {
+    params: Params,
+    source: S,
+    table: Table,
+    bits: u8,
+    // Предыдущий код
+    prev: Option<u32>,
+    // Распакованная строка и позиция первого не отданного символа
+    buf: Vec<u8>,
+    pos: usize,
+    // Поток закончился
+    done: bool,
+}
+
+impl<S: CodeSource> Decoder<S> {
+    pub fn new(params: Params, source: S) -> Self {
+        Decoder {
+            params,
+            source,
+            table: params.table(params.table_size, false),
+            bits: params.init_bits,
+            prev: None,
+            buf: Vec::new(),
+            pos: 0,
+            done: false,
+        }
+    }
+    pub fn into_source(self) -> S {
+        self.source
+    }
+    /// Считывает очередной код строки, выполняя встреченные управляющие коды.
+    /// Возвращает None в конце потока
+    fn read_code(&mut self) -> Result<Option<u32>> {
+        loop {
+            let code = match self.source.read_code(self.bits)? {
+                Some(code) => code,
+                None if self.params.end_required => {
+                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into())
+                }
+                None => return Ok(None),
+            };
+            if Some(code) == self.params.end {
+                return Ok(None);
+            } else if Some(code) == self.params.control {
+                match self.source.read_code(self.bits)? {
+                    Some(INC_CODE_SIZE) if self.bits < self.params.max_bits => self.bits += 1,
+                    Some(PARTIAL_CLEAR) => {
+                        self.table.partial_clear();
+                    }
+                    Some(code) => return Err(Error::InvalidCode(code)),
+                    None => return Ok(None),
+                }
+            } else if Some(code) == self.params.clear {
+                self.table.reset(self.params.first());
+                self.source.change_width();
+                self.bits = self.params.init_bits;
+                self.prev = None;
+            } else {
+                return Ok(Some(code));
+            }
+        }
+    }
+    /// Декодирует очередной код в buf. Возвращает false в конце потока
+    fn decode_next(&mut self) -> Result<bool> {
+        let code = match self.read_code()? {
+            Some(code) => code,
+            None => {
+                self.done = true;
+                return Ok(false);
+            }
+        };
+        // Первый код после начала или сброса строку не добавляет
+        let target = match self.prev {
+            Some(prev) => self
+                .table
+                .peek_free()
+                .map(|target| (target, Prev::Code(prev))),
+            None => None,
+        };
+        self.buf.clear();
+        self.pos = 0;
+        self.table.decode(code, target, &mut self.buf)?;
+        if let Some((inserted, _)) = target {
+            if self.params.control.is_none() && self.params.widens(self.bits, inserted + 1) {
+                self.bits += 1;
+                self.source.change_width();
+            }
+        }
+        self.prev = Some(code);
+        Ok(true)
+    }
+}
+
+impl<S: CodeSource> Read for Decoder<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Каждый код строки дает хотя бы один символ
+        if self.pos == self.buf.len() && (self.done || !self.decode_next()?) {
+            return Ok(0);
+        }
+        let len = buf.len().min(self.buf.len() - self.pos);
+        buf[..len].copy_from_slice(&self.buf[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
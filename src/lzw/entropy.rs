@@ -0,0 +1,395 @@
+//! Энтропийное кодирование кодов LZW
+//!
+//! Без него каждый код записывается `bits_count` битами, хотя одни коды
+//! встречаются заметно чаще других. Адаптивный двоичный интервальный кодер
+//! (range coder, как в LZMA) сжимает их дальше: старшие `TREE_BITS` бит кода
+//! кодируются деревом вероятностей, своим для каждой длины кода, остальные —
+//! с вероятностью 1/2. Перед каждым кодом кодируется признак конца потока,
+//! так как интервальный декодер читает данные с опережением и не может
+//! определить конец кодов по концу потока.
+use super::bits::{BitOrder, BitReader, BitWriter, MAX_CODE_BITS};
+use std::io::{self, Read, Write};
+
+/// Энтропийный кодер, записанный в заголовке архива
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entropy {
+    /// Адаптивный интервальный кодер: архив меньше на несколько процентов,
+    /// но сжатие и распаковка заметно медленнее
+    Range,
+}
+
+impl Entropy {
+    /// Идентификатор кодера в заголовке архива
+    pub fn id(self) -> u8 {
+        match self {
+            Entropy::Range => 1,
+        }
+    }
+    /// Кодер по идентификатору из заголовка
+    pub fn from_id(id: u8) -> Option<Entropy> {
+        match id {
+            1 => Some(Entropy::Range),
+            _ => None,
+        }
+    }
+}
+
+// Вероятность нуля хранится в PROB_BITS битах и после каждого бита
+// сдвигается к нему на 1/2^MOVE_BITS оставшегося расстояния
+const PROB_BITS: u32 = 11;
+const PROB_INIT: u16 = 1 << (PROB_BITS - 1);
+const MOVE_BITS: u32 = 5;
+// Интервал расширяется побайтно, когда становится меньше TOP
+const TOP: u32 = 1 << 24;
+/// Сколько старших бит кода кодируется деревом вероятностей
+const TREE_BITS: u8 = 10;
+
+/// Адаптивная модель: вероятности, общие для кодера и декодера
+struct Model {
+    // Вероятность того, что за признаком следует код, а не конец потока
+    end: u16,
+    // Дерево вероятностей старших бит для каждой длины кода,
+    // создается при первом коде этой длины
+    trees: Vec<Vec<u16>>,
+}
+
+impl Model {
+    fn new() -> Self {
+        Model {
+            end: PROB_INIT,
+            trees: vec![Vec::new(); MAX_CODE_BITS as usize + 1],
+        }
+    }
+    fn tree(&mut self, bits: u8) -> &mut Vec<u16> {
+        let tree = &mut self.trees[bits as usize];
+        if tree.is_empty() {
+            *tree = vec![PROB_INIT; 1 << bits.min(TREE_BITS)];
+        }
+        tree
+    }
+    fn update(prob: &mut u16, bit: u32) {
+        if bit == 0 {
+            *prob += ((1 << PROB_BITS) - *prob) >> MOVE_BITS;
+        } else {
+            *prob -= *prob >> MOVE_BITS;
+        }
+    }
+    /// Учитывает код длиной bits: проходит по дереву старших бит
+    fn update_code(&mut self, value: u32, bits: u8) {
+        let (high, low) = split(bits);
+        let tree = self.tree(bits);
+        let mut node = 1;
+        for i in (0..high).rev() {
+            let bit = (value >> (low + i)) & 1;
+            Model::update(&mut tree[node], bit);
+            node = (node << 1) | bit as usize;
+        }
+    }
+}
+
+/// Количество старших бит кода, кодируемых деревом, и младших
+fn split(bits: u8) -> (u8, u8) {
+    let high = bits.min(TREE_BITS);
+    (high, bits - high)
+}
+
+/// Интервальный кодер
+pub(crate) struct RangeEncoder {
+    low: u64,
+    range: u32,
+    // Последний байт, который еще может измениться из-за переноса,
+    // и количество таких байт вместе с ним
+    cache: u8,
+    cache_size: u64,
+    model: Model,
+}
+
+impl RangeEncoder {
+    fn new() -> Self {
+        RangeEncoder {
+            low: 0,
+            range: u32::MAX,
+            cache: 0,
+            cache_size: 1,
+            model: Model::new(),
+        }
+    }
+    fn shift_low<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        if (self.low as u32) < 0xFF00_0000 || self.low >> 32 != 0 {
+            let carry = (self.low >> 32) as u8;
+            let mut byte = self.cache;
+            while self.cache_size != 0 {
+                writer.write_all(&[byte.wrapping_add(carry)])?;
+                byte = 0xFF;
+                self.cache_size -= 1;
+            }
+            self.cache = (self.low >> 24) as u8;
+        }
+        self.cache_size += 1;
+        self.low = (self.low & 0x00FF_FFFF) << 8;
+        Ok(())
+    }
+    fn normalize<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        while self.range < TOP {
+            self.range <<= 8;
+            self.shift_low(writer)?;
+        }
+        Ok(())
+    }
+    fn encode_bit<W: Write>(&mut self, writer: &mut W, prob: u16, bit: u32) -> io::Result<()> {
+        let bound = (self.range >> PROB_BITS) * u32::from(prob);
+        if bit == 0 {
+            self.range = bound;
+        } else {
+            self.low += u64::from(bound);
+            self.range -= bound;
+        }
+        self.normalize(writer)
+    }
+    fn encode_direct<W: Write>(&mut self, writer: &mut W, value: u32, bits: u8) -> io::Result<()> {
+        for i in (0..bits).rev() {
+            self.range >>= 1;
+            if (value >> i) & 1 != 0 {
+                self.low += u64::from(self.range);
+            }
+            self.normalize(writer)?;
+        }
+        Ok(())
+    }
+    /// Кодирует признак: код или конец потока
+    fn encode_end<W: Write>(&mut self, writer: &mut W, end: bool) -> io::Result<()> {
+        let bit = end as u32;
+        self.encode_bit(writer, self.model.end, bit)?;
+        Model::update(&mut self.model.end, bit);
+        Ok(())
+    }
+    fn write_code<W: Write>(&mut self, writer: &mut W, value: u32, bits: u8) -> io::Result<()> {
+        self.encode_end(writer, false)?;
+        let (high, low) = split(bits);
+        let mut node = 1;
+        for i in (0..high).rev() {
+            let bit = (value >> (low + i)) & 1;
+            let prob = self.model.tree(bits)[node];
+            self.encode_bit(writer, prob, bit)?;
+            node = (node << 1) | bit as usize;
+        }
+        self.model.update_code(value, bits);
+        self.encode_direct(writer, value, low)
+    }
+    /// Кодирует конец потока и записывает оставшиеся байты интервала
+    fn finish<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.encode_end(writer, true)?;
+        for _ in 0..5 {
+            self.shift_low(writer)?;
+        }
+        Ok(())
+    }
+}
+
+/// Интервал декодера и текущее значение кода в нем
+#[derive(Clone, Copy)]
+struct Interval {
+    range: u32,
+    code: u32,
+}
+
+/// Результат попытки декодирования: None, если не хватило данных
+type Attempt<T> = io::Result<Option<T>>;
+
+/// Интервальный декодер. Если поток закончился посреди кода, состояние
+/// не меняется, а считанные байты сохраняются до следующего вызова
+pub(crate) struct RangeDecoder {
+    interval: Interval,
+    // Начальные байты интервала уже считаны
+    started: bool,
+    // Встретился конец потока
+    finished: bool,
+    // Байты, считанные при незавершенной попытке, и позиция в них
+    input: Vec<u8>,
+    pos: usize,
+    model: Model,
+}
+
+impl RangeDecoder {
+    fn new() -> Self {
+        RangeDecoder {
+            interval: Interval {
+                range: u32::MAX,
+                code: 0,
+            },
+            started: false,
+            finished: false,
+            input: Vec::new(),
+            pos: 0,
+            model: Model::new(),
+        }
+    }
+    fn next_byte<R: Read>(&mut self, reader: &mut R) -> Attempt<u8> {
+        if self.pos == self.input.len() {
+            let mut byte = [0u8; 1];
+            if reader.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            self.input.push(byte[0]);
+        }
+        self.pos += 1;
+        Ok(Some(self.input[self.pos - 1]))
+    }
+    fn normalize<R: Read>(&mut self, reader: &mut R, interval: &mut Interval) -> Attempt<()> {
+        while interval.range < TOP {
+            let byte = match self.next_byte(reader)? {
+                Some(byte) => byte,
+                None => return Ok(None),
+            };
+            interval.range <<= 8;
+            interval.code = (interval.code << 8) | u32::from(byte);
+        }
+        Ok(Some(()))
+    }
+    fn decode_bit<R: Read>(
+        &mut self,
+        reader: &mut R,
+        interval: &mut Interval,
+        prob: u16,
+    ) -> Attempt<u32> {
+        let bound = (interval.range >> PROB_BITS) * u32::from(prob);
+        let bit = if interval.code < bound {
+            interval.range = bound;
+            0
+        } else {
+            interval.code -= bound;
+            interval.range -= bound;
+            1
+        };
+        Ok(self.normalize(reader, interval)?.map(|_| bit))
+    }
+    fn decode_direct<R: Read>(&mut self, reader: &mut R, interval: &mut Interval) -> Attempt<u32> {
+        interval.range >>= 1;
+        let bit = if interval.code >= interval.range {
+            interval.code -= interval.range;
+            1
+        } else {
+            0
+        };
+        Ok(self.normalize(reader, interval)?.map(|_| bit))
+    }
+    /// Декодирует признак и код длиной bits на копии интервала. Вероятности
+    /// не меняются: каждая используется в коде не больше одного раза,
+    /// поэтому их можно обновить после успешной попытки.
+    /// Возвращает интервал и код, либо None вместо кода в конце потока
+    fn attempt<R: Read>(&mut self, reader: &mut R, bits: u8) -> Attempt<(Interval, Option<u32>)> {
+        let mut interval = self.interval;
+        if !self.started {
+            let mut head = [0u8; 5];
+            for byte in head.iter_mut() {
+                *byte = match self.next_byte(reader)? {
+                    Some(byte) => byte,
+                    None => return Ok(None),
+                };
+            }
+            // Первый байт кодера всегда нулевой
+            if head[0] != 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "неверное начало интервального кода",
+                ));
+            }
+            interval.code = u32::from_be_bytes([head[1], head[2], head[3], head[4]]);
+        }
+        let end = self.model.end;
+        match self.decode_bit(reader, &mut interval, end)? {
+            Some(1) => return Ok(Some((interval, None))),
+            Some(_) => {}
+            None => return Ok(None),
+        }
+        let (high, low) = split(bits);
+        let mut node = 1;
+        for _ in 0..high {
+            let prob = self.model.tree(bits)[node];
+            match self.decode_bit(reader, &mut interval, prob)? {
+                Some(bit) => node = (node << 1) | bit as usize,
+                None => return Ok(None),
+            }
+        }
+        let mut value = (node - (1 << high)) as u32;
+        for _ in 0..low {
+            match self.decode_direct(reader, &mut interval)? {
+                Some(bit) => value = (value << 1) | bit,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some((interval, Some(value))))
+    }
+    fn read_code<R: Read>(&mut self, reader: &mut R, bits: u8) -> Attempt<u32> {
+        if self.finished {
+            return Ok(None);
+        }
+        self.pos = 0;
+        let (interval, value) = match self.attempt(reader, bits)? {
+            Some(result) => result,
+            None => return Ok(None),
+        };
+        // Попытка удалась: все считанные байты использованы
+        self.input.clear();
+        self.started = true;
+        self.interval = interval;
+        Model::update(&mut self.model.end, value.is_none() as u32);
+        match value {
+            Some(value) => self.model.update_code(value, bits),
+            None => self.finished = true,
+        }
+        Ok(value)
+    }
+}
+
+/// Запись кодов: побитовая упаковка или энтропийный кодер
+pub(crate) enum CodeWriter {
+    Bits(BitWriter),
+    Range(RangeEncoder),
+}
+
+impl CodeWriter {
+    pub fn new(entropy: Option<Entropy>) -> Self {
+        match entropy {
+            None => CodeWriter::Bits(BitWriter::new(BitOrder::Msb)),
+            Some(Entropy::Range) => CodeWriter::Range(RangeEncoder::new()),
+        }
+    }
+    /// Записывает код из bits младших бит value
+    pub fn write_code<W: Write>(&mut self, writer: &mut W, value: u32, bits: u8) -> io::Result<()> {
+        match self {
+            CodeWriter::Bits(bits_writer) => bits_writer.write_bits(writer, value, bits),
+            CodeWriter::Range(encoder) => encoder.write_code(writer, value, bits),
+        }
+    }
+    /// Записывает все, что осталось в буфере, после последнего кода
+    pub fn finish<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        match self {
+            CodeWriter::Bits(bits_writer) => bits_writer.pad(writer),
+            CodeWriter::Range(encoder) => encoder.finish(writer),
+        }
+    }
+}
+
+/// Чтение кодов, записанных `CodeWriter`
+pub(crate) enum CodeReader {
+    Bits(BitReader),
+    Range(RangeDecoder),
+}
+
+impl CodeReader {
+    pub fn new(entropy: Option<Entropy>) -> Self {
+        match entropy {
+            None => CodeReader::Bits(BitReader::new(BitOrder::Msb)),
+            Some(Entropy::Range) => CodeReader::Range(RangeDecoder::new()),
+        }
+    }
+    /// Считывает код длиной bits. None, если поток закончился раньше,
+    /// либо встретился конец кодов
+    pub fn read_code<R: Read>(&mut self, reader: &mut R, bits: u8) -> io::Result<Option<u32>> {
+        match self {
+            CodeReader::Bits(bits_reader) => bits_reader.read_bits(reader, bits),
+            CodeReader::Range(decoder) => decoder.read_code(reader, bits),
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! Ошибки архиватора
+use std::fmt;
+use std::io;
+
+/// Результат операций архиватора
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Ошибка сжатия или распаковки
+#[derive(Debug)]
+pub enum Error {
+    /// Ошибка ввода-вывода
+    Io(io::Error),
+    /// Заголовок архива поврежден или не поддерживается
+    BadHeader(&'static str),
+    /// Недопустимый размер словаря (разрешенный: 9 <= n <= 32)
+    UnsupportedBits(usize),
+    /// В потоке встретился код, которого нет в словаре
+    InvalidCode(u32),
+    /// Архив оборвался раньше, чем ожидалось
+    Truncated,
+    /// Архив зашифрован, для распаковки требуется пароль
+    PasswordRequired,
+    /// Архив не зашифрован, а распаковка запрошена с паролем
+    NotEncrypted,
+    /// Неверное дополнение последнего блока AES: архив поврежден
+    /// или расшифрован не тем паролем
+    InvalidPadding,
+    /// Код аутентификации не совпал: зашифрованный архив изменен,
+    /// поврежден или пароль неверен
+    AuthenticationFailed,
+    /// Неверный пароль
+    WrongPassword,
+    /// Недопустимый параметр сжатия
+    InvalidOption(&'static str),
+    /// Недопустимое имя записи многофайлового архива: абсолютный путь,
+    /// выход за пределы каталога или повтор уже добавленного имени
+    InvalidEntryName(String),
+    /// Символическая ссылка из архива указывает за пределы каталога
+    /// распаковки, или путь записи проходит через символическую ссылку
+    UnsafeSymlink(String),
+    /// В многофайловом архиве нет записи с таким номером
+    NoSuchEntry(usize),
+    /// Контрольная сумма распакованных данных не совпадает с сохраненной
+    ChecksumMismatch,
+    /// Символ не входит в алфавит кодировщика, например индекс пикселя
+    /// не помещается в минимальный размер кода GIF
+    InvalidSymbol(u8),
+    /// Поток, сжимающий или распаковывающий блок, завершился аварийно
+    WorkerFailed,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "Ошибка ввода-вывода: {}", err),
+            Error::BadHeader(reason) => write!(f, "Неверный заголовок архива: {}", reason),
+            Error::UnsupportedBits(bits) => write!(
+                f,
+                "Недопустимый размер словаря: {}! Разрешенный: 9 <= n <= 32",
+                bits
+            ),
+            Error::InvalidCode(code) => write!(f, "Неверный зашифрованный код: {}", code),
+            Error::Truncated => write!(f, "Архив поврежден: неожиданный конец файла"),
+            Error::PasswordRequired => write!(f, "Архив зашифрован, требуется пароль"),
+            Error::NotEncrypted => write!(f, "Архив не зашифрован"),
+            Error::InvalidPadding => write!(f, "Архив поврежден: неверное дополнение блока"),
+            Error::AuthenticationFailed => write!(
+                f,
+                "Архив изменен или поврежден: код аутентификации не совпадает"
+            ),
+            Error::WrongPassword => write!(f, "Неверный пароль"),
+            Error::InvalidOption(reason) => write!(f, "Недопустимый параметр: {}", reason),
+            Error::InvalidEntryName(name) => write!(f, "Недопустимое имя записи: {}", name),
+            Error::UnsafeSymlink(name) => write!(
+                f,
+                "Запись выходит за пределы каталога распаковки через символическую ссылку: {}",
+                name
+            ),
+            Error::NoSuchEntry(index) => write!(f, "В архиве нет записи с номером {}", index),
+            Error::ChecksumMismatch => {
+                write!(f, "Архив поврежден: контрольная сумма не совпадает")
+            }
+            Error::InvalidSymbol(symbol) => write!(f, "Недопустимый символ: {}", symbol),
+            Error::WorkerFailed => write!(f, "Поток обработки блока завершился аварийно"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        // Ошибка архиватора, прошедшая через Read/Write, извлекается обратно
+        if err.get_ref().is_some_and(|inner| inner.is::<Error>()) {
+            let inner = err.into_inner().unwrap();
+            return *inner.downcast::<Error>().unwrap();
+        }
+        match err.kind() {
+            io::ErrorKind::UnexpectedEof => Error::Truncated,
+            _ => Error::Io(err),
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> io::Error {
+        match err {
+            Error::Io(err) => err,
+            Error::Truncated => io::Error::new(io::ErrorKind::UnexpectedEof, err),
+            err => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
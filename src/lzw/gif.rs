@@ -0,0 +1,223 @@
+//! LZW в варианте GIF
+//!
+//! Алфавит задается минимальным размером кода m (2..=8): литералы 0..2^m,
+//! затем CLEAR = 2^m и EOI = 2^m + 1. Коды начинаются с m + 1 бит, растут
+//! до 12 и записываются младшим битом вперед. В файле GIF сжатые данные
+//! изображения предваряются байтом m и разбиваются на подблоки
+//! до 255 байт, каждый со своей длиной; последовательность подблоков
+//! завершается пустым блоком. Строки хранятся в том же словаре, что
+//! и в основном формате, отличаются только алфавит и коды CLEAR и EOI.
+use super::bits::BitOrder;
+use super::codec::{BitSink, BitSource, Decoder, Encoder, Full, Params};
+use super::error::{Error, Result};
+use std::io::{self, Read, Write};
+
+/// Наибольшая разрядность кода
+pub const MAX_BITS: u8 = 12;
+/// Размер словаря
+const TABLE_SIZE: u32 = 1 << MAX_BITS;
+/// Наибольшая длина подблока
+pub const MAX_BLOCK_LEN: usize = 255;
+
+/// Проверяет минимальный размер кода
+fn check_code_size(min_code_size: u8) -> Result<u8> {
+    if !(2..=8).contains(&min_code_size) {
+        return Err(Error::InvalidOption(
+            "минимальный размер кода GIF должен быть от 2 до 8 бит",
+        ));
+    }
+    Ok(min_code_size)
+}
+
+/// Параметры ядра LZW для минимального размера кода min_code_size
+fn params(min_code_size: u8) -> Params {
+    let clear = 1 << min_code_size;
+    Params {
+        literals: clear,
+        clear: Some(clear),
+        end: Some(clear + 1),
+        end_required: true,
+        control: None,
+        init_bits: min_code_size + 1,
+        max_bits: MAX_BITS,
+        table_size: TABLE_SIZE,
+        spare: 0,
+        early_change: false,
+        full: Full::Clear,
+    }
+}
+
+/// Кодировщик GIF LZW, реализующий `Write`: принимает индексы пикселей
+/// и выдает коды без разбиения на подблоки. Поток начинается с CLEAR;
+/// при заполнении словаря выдается CLEAR и словарь начинается заново.
+///
+/// После записи всех данных необходимо вызвать `finish`, который допишет EOI.
+pub struct GifEncoder<W: Write> {
+    encoder: Encoder<BitSink<W>>,
+}
+
+impl<W: Write> GifEncoder<W> {
+    /// Создает кодировщик с минимальным размером кода min_code_size
+    pub fn new(writer: W, min_code_size: u8) -> Result<Self> {
+        let params = params(check_code_size(min_code_size)?);
+        let mut encoder = Encoder::new(params, BitSink::new(writer, BitOrder::Lsb));
+        encoder.clear()?;
+        Ok(GifEncoder { encoder })
+    }
+    /// Записывает последний код, EOI и неполный байт, возвращает вложенный поток
+    pub fn finish(self) -> Result<W> {
+        self.encoder.finish()?.finish()
+    }
+}
+
+impl<W: Write> Write for GifEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.encoder.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder.flush()
+    }
+}
+
+/// Декодировщик GIF LZW, реализующий `Read`: читает коды без подблоков
+/// и отдает индексы пикселей до кода EOI.
+///
+/// Словарь, заполненный без последующего CLEAR, просто перестает расти,
+/// как это допускают декодеры GIF.
+pub struct GifDecoder<R: Read> {
+    decoder: Decoder<BitSource<R>>,
+}
+
+impl<R: Read> GifDecoder<R> {
+    /// Создает декодировщик с минимальным размером кода min_code_size
+    pub fn new(reader: R, min_code_size: u8) -> Result<Self> {
+        let params = params(check_code_size(min_code_size)?);
+        Ok(GifDecoder {
+            decoder: Decoder::new(params, BitSource::new(reader, BitOrder::Lsb)),
+        })
+    }
+    /// Возвращает вложенный поток. Данные после EOI, уже считанные
+    /// в буфер, теряются
+    pub fn into_inner(self) -> R {
+        self.decoder.into_source().into_inner()
+    }
+}
+
+impl<R: Read> Read for GifDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.decoder.read(buf)
+    }
+}
+
+/// Разбивает записываемые данные на подблоки GIF.
+///
+/// `finish` записывает последний неполный подблок и завершающий пустой блок.
+pub struct SubBlockWriter<W: Write> {
+    writer: W,
+    block: Vec<u8>,
+}
+
+impl<W: Write> SubBlockWriter<W> {
+    pub fn new(writer: W) -> Self {
+        SubBlockWriter {
+            writer,
+            block: Vec::with_capacity(MAX_BLOCK_LEN),
+        }
+    }
+    /// Записывает оставшиеся данные и завершающий блок, возвращает вложенный поток
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_block()?;
+        self.writer.write_all(&[0])?;
+        Ok(self.writer)
+    }
+    fn write_block(&mut self) -> io::Result<()> {
+        if !self.block.is_empty() {
+            self.writer.write_all(&[self.block.len() as u8])?;
+            self.writer.write_all(&self.block)?;
+            self.block.clear();
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for SubBlockWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(MAX_BLOCK_LEN - self.block.len());
+        self.block.extend_from_slice(&buf[..len]);
+        if self.block.len() == MAX_BLOCK_LEN {
+            self.write_block()?;
+        }
+        Ok(len)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Читает данные из последовательности подблоков GIF до завершающего
+/// пустого блока. Из вложенного потока не считывается ничего сверх него,
+/// поэтому после чтения тот указывает на следующий блок файла GIF
+pub struct SubBlockReader<R: Read> {
+    reader: R,
+    // Непрочитанный остаток текущего подблока
+    remaining: usize,
+    done: bool,
+}
+
+impl<R: Read> SubBlockReader<R> {
+    pub fn new(reader: R) -> Self {
+        SubBlockReader {
+            reader,
+            remaining: 0,
+            done: false,
+        }
+    }
+    /// Возвращает вложенный поток
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Read for SubBlockReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 && !self.done {
+            let mut len = [0u8; 1];
+            self.reader.read_exact(&mut len)?;
+            self.remaining = len[0] as usize;
+            self.done = self.remaining == 0;
+        }
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+        let len = buf.len().min(self.remaining);
+        let bytes_read = self.reader.read(&mut buf[..len])?;
+        if bytes_read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.remaining -= bytes_read;
+        Ok(bytes_read)
+    }
+}
+
+/// Сжимает индексы пикселей в данные изображения GIF: байт минимального
+/// размера кода и подблоки со сжатыми кодами
+pub fn encode_image_data<W: Write>(pixels: &[u8], min_code_size: u8, mut writer: W) -> Result<W> {
+    check_code_size(min_code_size)?;
+    writer.write_all(&[min_code_size])?;
+    let mut encoder = GifEncoder::new(SubBlockWriter::new(writer), min_code_size)?;
+    encoder.write_all(pixels)?;
+    Ok(encoder.finish()?.finish()?)
+}
+/// Распаковывает данные изображения GIF, дописывая индексы пикселей в конец out.
+/// Поток считывается до завершающего пустого блока включительно
+pub fn decode_image_data<R: Read>(mut reader: R, out: &mut Vec<u8>) -> Result<()> {
+    let mut min_code_size = [0u8; 1];
+    reader.read_exact(&mut min_code_size)?;
+    let min_code_size = check_code_size(min_code_size[0])
+        .map_err(|_| Error::BadHeader("недопустимый минимальный размер кода GIF"))?;
+    let mut decoder = GifDecoder::new(SubBlockReader::new(reader), min_code_size)?;
+    decoder.read_to_end(out)?;
+    // Остаток подблоков после EOI пропускается
+    io::copy(&mut decoder.into_inner(), &mut io::sink())?;
+    Ok(())
+}
@@ -0,0 +1,231 @@
+//! Заголовок архива: сигнатура, версия формата, размер словаря, флаги
+//! и параметры получения ключа для зашифрованных архивов
+use super::checksum::Checksum;
+use super::entropy::Entropy;
+use super::error::{Error, Result};
+use std::io::{Read, Write};
+
+/// Сигнатура, с которой начинается каждый архив
+pub const MAGIC: [u8; 4] = *b"LZW\x1a";
+/// Текущая версия формата
+pub const VERSION: u8 = 1;
+/// Размер основной части заголовка в байтах
+pub const HEADER_LEN: usize = 7;
+/// Размер параметров получения ключа в байтах
+pub const KDF_LEN: usize = 1 + 4 + SALT_LEN;
+/// Длина соли в байтах
+pub const SALT_LEN: usize = 16;
+/// Алгоритм получения ключа: PBKDF2-HMAC-SHA256
+pub const KDF_PBKDF2_SHA256: u8 = 1;
+/// Данные после заголовка зашифрованы AES
+pub const FLAG_ENCRYPTED: u8 = 0b0000_0001;
+/// В архиве хранится контрольная сумма исходных данных, см. [`Checksum`]
+pub const FLAG_CHECKSUM: u8 = 0b0000_0010;
+/// Заполненный словарь не сбрасывается, а вытесняет строки,
+/// которые дольше всех не использовались (LZT)
+pub const FLAG_LZT: u8 = 0b0000_0100;
+/// Заполненный словарь сбрасывается кодом CLEAR, когда ухудшается степень сжатия
+pub const FLAG_CLEAR: u8 = 0b0000_1000;
+/// Данные сжаты независимыми блоками, за ними следует индекс блоков
+pub const FLAG_BLOCKS: u8 = 0b0001_0000;
+/// Коды сжаты энтропийным кодером, см. [`Entropy`]
+pub const FLAG_ENTROPY: u8 = 0b0010_0000;
+/// Флаги, которые умеет обрабатывать текущая версия
+const SUPPORTED_FLAGS: u8 =
+    FLAG_ENCRYPTED | FLAG_CHECKSUM | FLAG_LZT | FLAG_CLEAR | FLAG_BLOCKS | FLAG_ENTROPY;
+
+/// Заголовок архива
+///
+/// Формат: `MAGIC` (4 байта), версия (1 байт), максимальное
+/// количество бит в словаре (1 байт), флаги (1 байт).
+/// Если установлен `FLAG_ENCRYPTED`, далее следуют параметры [`Kdf`],
+/// если установлен `FLAG_CHECKSUM` — идентификатор алгоритма контрольной
+/// суммы (1 байт), если установлен `FLAG_ENTROPY` — идентификатор
+/// энтропийного кодера (1 байт).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    /// Версия формата
+    pub version: u8,
+    /// Максимальное количество бит, т.е. размер словаря
+    pub max_bits_count: u8,
+    /// Набор флагов `FLAG_*`
+    pub flags: u8,
+    /// Параметры получения ключа (только для зашифрованных архивов)
+    pub kdf: Option<Kdf>,
+    /// Алгоритм контрольной суммы (только если она хранится в архиве)
+    pub checksum: Option<Checksum>,
+    /// Энтропийный кодер кодов (только если он используется)
+    pub entropy: Option<Entropy>,
+}
+
+/// Параметры получения ключа из пароля
+///
+/// Формат: алгоритм `KDF_*` (1 байт), количество итераций
+/// (4 байта, little-endian), соль (`SALT_LEN` байт).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Kdf {
+    /// Алгоритм `KDF_*`
+    pub algorithm: u8,
+    /// Количество итераций, т.е. трудоемкость подбора пароля
+    pub iterations: u32,
+    /// Случайная соль, своя для каждого архива
+    pub salt: [u8; SALT_LEN],
+}
+
+impl Kdf {
+    /// Записывает параметры в поток
+    fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let mut buf = [0u8; KDF_LEN];
+        buf[0] = self.algorithm;
+        buf[1..5].copy_from_slice(&self.iterations.to_le_bytes());
+        buf[5..].copy_from_slice(&self.salt);
+        writer.write_all(&buf)?;
+        Ok(())
+    }
+    /// Считывает и проверяет параметры из потока
+    fn read<R: Read>(reader: &mut R) -> Result<Kdf> {
+        let mut buf = [0u8; KDF_LEN];
+        reader.read_exact(&mut buf)?;
+        let mut iterations = [0u8; 4];
+        iterations.copy_from_slice(&buf[1..5]);
+        let mut salt = [0u8; SALT_LEN];
+        salt.copy_from_slice(&buf[5..]);
+        let kdf = Kdf {
+            algorithm: buf[0],
+            iterations: u32::from_le_bytes(iterations),
+            salt,
+        };
+        if kdf.algorithm != KDF_PBKDF2_SHA256 {
+            return Err(Error::BadHeader("неизвестный алгоритм получения ключа"));
+        }
+        if kdf.iterations == 0 {
+            return Err(Error::BadHeader("нулевое количество итераций"));
+        }
+        Ok(kdf)
+    }
+}
+
+impl Header {
+    /// Создает заголовок текущей версии
+    pub fn new(max_bits_count: u8, flags: u8) -> Self {
+        Header {
+            version: VERSION,
+            max_bits_count,
+            flags,
+            kdf: None,
+            checksum: None,
+            entropy: None,
+        }
+    }
+    /// Задает параметры получения ключа и помечает архив как зашифрованный
+    pub fn with_kdf(mut self, kdf: Kdf) -> Self {
+        self.flags |= FLAG_ENCRYPTED;
+        self.kdf = Some(kdf);
+        self
+    }
+    /// Задает алгоритм контрольной суммы исходных данных, если он выбран
+    pub fn with_checksum(mut self, checksum: Option<Checksum>) -> Self {
+        if checksum.is_some() {
+            self.flags |= FLAG_CHECKSUM;
+        }
+        self.checksum = checksum;
+        self
+    }
+    /// Задает энтропийный кодер кодов, если он выбран
+    pub fn with_entropy(mut self, entropy: Option<Entropy>) -> Self {
+        if entropy.is_some() {
+            self.flags |= FLAG_ENTROPY;
+        }
+        self.entropy = entropy;
+        self
+    }
+    /// Данные архива зашифрованы?
+    pub fn is_encrypted(&self) -> bool {
+        self.flags & FLAG_ENCRYPTED != 0
+    }
+    /// В архиве хранится контрольная сумма?
+    pub fn has_checksum(&self) -> bool {
+        self.flags & FLAG_CHECKSUM != 0
+    }
+    /// Данные сжаты независимыми блоками?
+    pub fn has_blocks(&self) -> bool {
+        self.flags & FLAG_BLOCKS != 0
+    }
+    /// Записывает заголовок в поток
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let mut buf = [0u8; HEADER_LEN];
+        buf[..MAGIC.len()].copy_from_slice(&MAGIC);
+        buf[4] = self.version;
+        buf[5] = self.max_bits_count;
+        buf[6] = self.flags;
+        writer.write_all(&buf)?;
+        if self.is_encrypted() {
+            self.kdf
+                .ok_or(Error::BadHeader("не заданы параметры получения ключа"))?
+                .write(writer)?;
+        }
+        if self.has_checksum() {
+            let checksum = self
+                .checksum
+                .ok_or(Error::BadHeader("не задан алгоритм контрольной суммы"))?;
+            writer.write_all(&[checksum.id()])?;
+        }
+        if self.flags & FLAG_ENTROPY != 0 {
+            let entropy = self
+                .entropy
+                .ok_or(Error::BadHeader("не задан энтропийный кодер"))?;
+            writer.write_all(&[entropy.id()])?;
+        }
+        Ok(())
+    }
+    /// Считывает и проверяет заголовок из потока
+    pub fn read<R: Read>(reader: &mut R) -> Result<Header> {
+        let mut buf = [0u8; HEADER_LEN];
+        reader.read_exact(&mut buf)?;
+        if buf[..MAGIC.len()] != MAGIC {
+            return Err(Error::BadHeader("файл не является LZW архивом"));
+        }
+        let mut header = Header {
+            version: buf[4],
+            max_bits_count: buf[5],
+            flags: buf[6],
+            kdf: None,
+            checksum: None,
+            entropy: None,
+        };
+        if header.version != VERSION {
+            return Err(Error::BadHeader("неподдерживаемая версия формата"));
+        }
+        if !(9..=32).contains(&header.max_bits_count) {
+            return Err(Error::UnsupportedBits(header.max_bits_count as usize));
+        }
+        if header.flags & !SUPPORTED_FLAGS != 0 {
+            return Err(Error::BadHeader("неподдерживаемые флаги"));
+        }
+        if header.flags & (FLAG_LZT | FLAG_CLEAR) == FLAG_LZT | FLAG_CLEAR {
+            return Err(Error::BadHeader("несовместимые режимы словаря"));
+        }
+        if header.is_encrypted() && header.has_blocks() {
+            return Err(Error::BadHeader("блочный архив не может быть зашифрован"));
+        }
+        if header.is_encrypted() {
+            header.kdf = Some(Kdf::read(reader)?);
+        }
+        if header.has_checksum() {
+            let mut id = [0u8; 1];
+            reader.read_exact(&mut id)?;
+            header.checksum = Some(
+                Checksum::from_id(id[0])
+                    .ok_or(Error::BadHeader("неизвестный алгоритм контрольной суммы"))?,
+            );
+        }
+        if header.flags & FLAG_ENTROPY != 0 {
+            let mut id = [0u8; 1];
+            reader.read_exact(&mut id)?;
+            header.entropy = Some(
+                Entropy::from_id(id[0]).ok_or(Error::BadHeader("неизвестный энтропийный кодер"))?,
+            );
+        }
+        Ok(header)
+    }
+}
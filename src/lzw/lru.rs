@@ -0,0 +1,89 @@
+//! Учет давности использования строк словаря для режима LZT
+//!
+//! Когда словарь заполнен, новая строка занимает индекс строки, которая
+//! дольше всех не использовалась. Вытесняются только листья, т.е. строки,
+//! которые не служат префиксом других: тогда префикс любой строки словаря
+//! тоже остается в словаре. Кодировщик и декодер ведут учет одинаково,
+//! поэтому выбирают одни и те же индексы.
+use super::Index;
+use std::collections::BTreeMap;
+
+/// Первый индекс, который может быть вытеснен: литералы остаются всегда
+const FIRST: usize = 256;
+
+pub struct Lru {
+    // Счетчик обращений, отметка времени для следующего обращения
+    clock: u64,
+    // Время последнего обращения к каждой строке
+    stamp: Vec<u64>,
+    // Индекс префикса каждой строки
+    parent: Vec<Index>,
+    // Количество строк, для которых строка служит префиксом
+    children: Vec<u32>,
+    // Листья, упорядоченные по времени последнего обращения
+    leaves: BTreeMap<u64, Index>,
+}
+
+impl Lru {
+    pub fn new() -> Self {
+        Lru {
+            clock: 0,
+            stamp: vec![0; FIRST],
+            parent: vec![0; FIRST],
+            children: vec![0; FIRST],
+            leaves: BTreeMap::new(),
+        }
+    }
+    fn is_leaf(&self, index: usize) -> bool {
+        index >= FIRST && self.children[index] == 0
+    }
+    /// Отмечает обращение к строке
+    pub fn touch(&mut self, index: Index) {
+        let index = index as usize;
+        if self.is_leaf(index) {
+            self.leaves.remove(&self.stamp[index]);
+            self.leaves.insert(self.clock, index as Index);
+        }
+        self.stamp[index] = self.clock;
+        self.clock += 1;
+    }
+    /// Индекс, который займет новая строка с заданным префиксом в заполненном
+    /// словаре. None, если вытеснить можно только сам префикс: тогда новая
+    /// строка не добавляется
+    pub fn victim(&self, prefix: Index) -> Option<Index> {
+        self.leaves
+            .values()
+            .find(|&&index| index != prefix)
+            .cloned()
+    }
+    /// Удаляет строку из учета перед тем, как ее индекс займет новая строка
+    pub fn evict(&mut self, index: Index) {
+        let index = index as usize;
+        self.leaves.remove(&self.stamp[index]);
+        let parent = self.parent[index] as usize;
+        self.children[parent] -= 1;
+        // Префикс, лишившийся последнего продолжения, сам становится листом
+        if self.is_leaf(parent) {
+            self.leaves.insert(self.stamp[parent], parent as Index);
+        }
+    }
+    /// Учитывает новую строку prefix + C под индексом index
+    pub fn insert(&mut self, index: Index, prefix: Index) {
+        let position = index as usize;
+        if position == self.stamp.len() {
+            self.stamp.push(0);
+            self.parent.push(0);
+            self.children.push(0);
+        }
+        let parent = prefix as usize;
+        if self.is_leaf(parent) {
+            self.leaves.remove(&self.stamp[parent]);
+        }
+        self.children[parent] += 1;
+        self.parent[position] = prefix;
+        self.children[position] = 0;
+        self.stamp[position] = self.clock;
+        self.leaves.insert(self.clock, index);
+        self.clock += 1;
+    }
+}
@@ -0,0 +1,265 @@
+//! Формат `.Z` утилиты compress(1) (LZC)
+//!
+//! Заголовок: `1f 9d` и байт флагов (младшие 5 бит — максимальный размер
+//! словаря, старший бит — блочный режим с кодом CLEAR = 256). Кодовые слова
+//! записываются младшим битом вперед, начиная с 9 бит. Коды группируются
+//! по n_bits байт (восемь кодов); при увеличении разрядности и после CLEAR
+//! незаполненная группа дописывается до конца, и декодер ее пропускает.
+use super::codec::{CodeSink, CodeSource, Decoder, Encoder, Full, Params};
+use super::error::{Error, Result};
+use super::read_block;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+/// Сигнатура файлов `.Z`
+pub const MAGIC: [u8; 2] = [0x1f, 0x9d];
+/// Флаг блочного режима: поддерживается код CLEAR
+const BLOCK_MODE: u8 = 0x80;
+/// Маска максимального размера словаря в байте флагов
+const BIT_MASK: u8 = 0x1f;
+/// Начальная разрядность кодов
+const INIT_BITS: u8 = 9;
+/// Наибольший размер словаря, поддерживаемый compress(1)
+pub const MAX_BITS: u8 = 16;
+/// Код сброса словаря
+const CLEAR: u32 = 256;
+/// Через сколько входных байт проверяется степень сжатия при полном словаре
+const CHECK_GAP: u64 = 10_000;
+
+/// Параметры ядра LZW. Начальная разрядность всегда считается неполной,
+/// поэтому при max_bits = 9 compress(1) все равно переходит к 10 битам,
+/// и здесь так же
+fn params(max_bits: u8, block_mode: bool) -> Params {
+    Params {
+        literals: 256,
+        clear: if block_mode { Some(CLEAR) } else { None },
+        end: None,
+        end_required: false,
+        control: None,
+        init_bits: INIT_BITS,
+        max_bits: max_bits.max(INIT_BITS + 1),
+        table_size: 1 << max_bits,
+        spare: 0,
+        early_change: false,
+        full: Full::Keep,
+    }
+}
+
+/// Упаковка кодов группами: группа из n_bits байт вмещает восемь кодов
+/// разрядности n_bits
+struct GroupWriter<W: Write> {
+    writer: BufWriter<W>,
+    // Группа, заполненное в ней количество бит и разрядность ее кодов
+    group: [u8; MAX_BITS as usize],
+    offset: usize,
+    n_bits: usize,
+    // Количество записанных байт с учетом заголовка
+    bytes_out: u64,
+}
+
+impl<W: Write> GroupWriter<W> {
+    /// Записывает последнюю группу и возвращает вложенный поток
+    fn finish(mut self) -> Result<W> {
+        // В конце группа не дополняется: записываются только занятые байты
+        if self.offset > 0 {
+            let len = self.offset.div_ceil(8);
+            self.writer.write_all(&self.group[..len])?;
+        }
+        Ok(self.writer.into_inner().map_err(|err| err.into_error())?)
+    }
+    fn flush_group(&mut self) -> io::Result<()> {
+        self.writer.write_all(&self.group[..self.n_bits])?;
+        self.bytes_out += self.n_bits as u64;
+        self.group = [0; MAX_BITS as usize];
+        self.offset = 0;
+        Ok(())
+    }
+}
+
+impl<W: Write> CodeSink for GroupWriter<W> {
+    fn write_code(&mut self, code: u32, bits: u8) -> io::Result<()> {
+        self.n_bits = bits as usize;
+        for bit in 0..self.n_bits {
+            if code >> bit & 1 != 0 {
+                let pos = self.offset + bit;
+                self.group[pos / 8] |= 1 << (pos % 8);
+            }
+        }
+        self.offset += self.n_bits;
+        if self.offset == self.n_bits * 8 {
+            self.flush_group()?;
+        }
+        Ok(())
+    }
+    /// Неполная группа дописывается целиком
+    fn change_width(&mut self) -> io::Result<()> {
+        if self.offset > 0 {
+            self.flush_group()?;
+        }
+        Ok(())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Чтение кодов группами. Новая группа читается, когда текущая
+/// исчерпана, сменилась разрядность или встретился CLEAR
+struct GroupReader<R: Read> {
+    reader: BufReader<R>,
+    // Группа, количество бит с целыми кодами в ней и позиция
+    group: [u8; MAX_BITS as usize],
+    size: usize,
+    offset: usize,
+}
+
+impl<R: Read> CodeSource for GroupReader<R> {
+    fn read_code(&mut self, bits: u8) -> io::Result<Option<u32>> {
+        let n_bits = bits as usize;
+        if self.offset >= self.size {
+            let len = read_block(&mut self.reader, &mut self.group[..n_bits])?;
+            // Хвост короче одного кода не содержит данных
+            if len * 8 < n_bits {
+                return Ok(None);
+            }
+            self.offset = 0;
+            // Количество бит, с которых еще может начаться целый код
+            self.size = len * 8 - (n_bits - 1);
+        }
+        let mut code = 0;
+        for bit in 0..n_bits {
+            let pos = self.offset + bit;
+            code |= u32::from(self.group[pos / 8] >> (pos % 8) & 1) << bit;
+        }
+        self.offset += n_bits;
+        Ok(Some(code))
+    }
+    /// Остаток группы пропускается
+    fn change_width(&mut self) {
+        self.offset = self.size;
+    }
+}
+
+/// Кодировщик `.Z`, реализующий `Write`. Всегда пишет в блочном режиме:
+/// когда словарь заполнен и степень сжатия падает, выдается CLEAR.
+///
+/// После записи всех данных необходимо вызвать `finish`.
+pub struct ZEncoder<W: Write> {
+    encoder: Encoder<GroupWriter<W>>,
+    // Счетчики для решения о сбросе словаря
+    in_count: u64,
+    checkpoint: u64,
+    ratio: u64,
+}
+
+impl<W: Write> ZEncoder<W> {
+    /// Создает кодировщик с размером словаря max_bits (9..=16) и записывает заголовок
+    pub fn new(writer: W, max_bits: u8) -> Result<Self> {
+        if !(INIT_BITS..=MAX_BITS).contains(&max_bits) {
+            return Err(Error::InvalidOption(
+                "формат .Z поддерживает размер словаря от 9 до 16 бит",
+            ));
+        }
+        let mut writer = BufWriter::new(writer);
+        let header = [MAGIC[0], MAGIC[1], max_bits | BLOCK_MODE];
+        writer.write_all(&header)?;
+        let sink = GroupWriter {
+            writer,
+            group: [0; MAX_BITS as usize],
+            offset: 0,
+            n_bits: INIT_BITS as usize,
+            bytes_out: header.len() as u64,
+        };
+        Ok(ZEncoder {
+            encoder: Encoder::new(params(max_bits, true), sink),
+            in_count: 0,
+            checkpoint: CHECK_GAP,
+            ratio: 0,
+        })
+    }
+    /// Записывает последний код и неполную группу, возвращает вложенный поток
+    pub fn finish(self) -> Result<W> {
+        self.encoder.finish()?.finish()
+    }
+    /// Сбрасывает заполненный словарь, если степень сжатия перестала расти
+    fn check_ratio(&mut self) -> io::Result<()> {
+        self.checkpoint = self.in_count + CHECK_GAP;
+        let bytes_out = self.encoder.sink().bytes_out;
+        // Отношение входа к выходу с фиксированной точкой (8 бит дробной части)
+        let ratio = if self.in_count > 0x007f_ffff {
+            match bytes_out >> 8 {
+                0 => 0x7fff_ffff,
+                out => self.in_count / out,
+            }
+        } else {
+            (self.in_count << 8) / bytes_out
+        };
+        if ratio > self.ratio {
+            self.ratio = ratio;
+        } else {
+            self.ratio = 0;
+            self.encoder.clear()?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for ZEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf {
+            self.in_count += 1;
+            if self.encoder.push(byte)? && self.in_count >= self.checkpoint {
+                self.check_ratio()?;
+            }
+        }
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder.flush()
+    }
+}
+
+/// Декодировщик `.Z`, реализующий `Read`. Понимает и блочный режим,
+/// и старые файлы без кода CLEAR.
+///
+/// Заголовок считывается и проверяется при создании.
+pub struct ZDecoder<R: Read> {
+    decoder: Decoder<GroupReader<R>>,
+    max_bits: u8,
+}
+
+impl<R: Read> ZDecoder<R> {
+    /// Создает декодировщик, считывая заголовок из reader
+    pub fn new(reader: R) -> Result<Self> {
+        let mut reader = BufReader::new(reader);
+        let mut header = [0u8; 3];
+        reader.read_exact(&mut header)?;
+        if header[..2] != MAGIC {
+            return Err(Error::BadHeader("неверная сигнатура .Z"));
+        }
+        let max_bits = header[2] & BIT_MASK;
+        if !(INIT_BITS..=MAX_BITS).contains(&max_bits) {
+            return Err(Error::BadHeader("недопустимый размер словаря .Z"));
+        }
+        let block_mode = header[2] & BLOCK_MODE != 0;
+        let source = GroupReader {
+            reader,
+            group: [0; MAX_BITS as usize],
+            size: 0,
+            offset: 0,
+        };
+        Ok(ZDecoder {
+            decoder: Decoder::new(params(max_bits, block_mode), source),
+            max_bits,
+        })
+    }
+    /// Максимальный размер словаря из заголовка
+    pub fn max_bits(&self) -> u8 {
+        self.max_bits
+    }
+}
+
+impl<R: Read> Read for ZDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.decoder.read(buf)
+    }
+}
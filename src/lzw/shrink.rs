@@ -0,0 +1,105 @@
+//! Метод сжатия Shrink (метод 1 в ZIP)
+//!
+//! Динамический LZW: коды записываются младшим битом вперед, начиная
+//! с 9 бит и до 13. Код 256 — управляющий, за ним следует подкод той же
+//! разрядности: 1 увеличивает разрядность на единицу, 2 выполняет частичный
+//! сброс — освобождает все строки, которые не являются префиксом других.
+//! Новая строка получает наименьший свободный код начиная с 257. Признака
+//! конца потока нет: его размер известен из заголовка записи ZIP.
+//!
+//! Словарь и частичный сброс — общие с остальными форматами (`codec::Table`).
+//! Освобожденный код может оказаться префиксом новой строки и затем получить
+//! другую строку: тогда меняется и продолжение, как в unzip от Info-ZIP.
+use super::bits::BitOrder;
+use super::codec::{BitSink, BitSource, Decoder, Encoder, Full, Params};
+use super::error::Result;
+use std::io::{self, Read, Write};
+
+/// Управляющий код
+const CONTROL: u32 = 256;
+/// Начальная разрядность кодов
+const INIT_BITS: u8 = 9;
+/// Наибольшая разрядность кодов
+pub const MAX_BITS: u8 = 13;
+
+/// Параметры ядра LZW
+const PARAMS: Params = Params {
+    literals: 256,
+    clear: None,
+    end: None,
+    end_required: false,
+    control: Some(CONTROL),
+    init_bits: INIT_BITS,
+    max_bits: MAX_BITS,
+    table_size: 1 << MAX_BITS,
+    spare: 0,
+    early_change: false,
+    full: Full::PartialClear,
+};
+
+/// Кодировщик Shrink, реализующий `Write`. Разрядность увеличивается,
+/// когда очередной код в нее не помещается; когда свободные коды
+/// заканчиваются, выполняется частичный сброс.
+///
+/// После записи всех данных необходимо вызвать `finish`.
+pub struct ShrinkEncoder<W: Write> {
+    encoder: Encoder<BitSink<W>>,
+}
+
+impl<W: Write> ShrinkEncoder<W> {
+    pub fn new(writer: W) -> Self {
+        ShrinkEncoder {
+            encoder: Encoder::new(PARAMS, BitSink::new(writer, BitOrder::Lsb)),
+        }
+    }
+    /// Записывает последний код и неполный байт, возвращает вложенный поток
+    pub fn finish(self) -> Result<W> {
+        self.encoder.finish()?.finish()
+    }
+}
+
+impl<W: Write> Write for ShrinkEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.encoder.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder.flush()
+    }
+}
+
+/// Декодировщик Shrink (unshrink), реализующий `Read`: читает сжатые
+/// данные записи ZIP до конца вложенного потока. Вложенный поток должен
+/// заканчиваться вместе с записью, например `Read::take` по сжатому размеру.
+///
+/// Последний байт потока дополнен нулями, поэтому неполный код означает
+/// конец данных
+pub struct ShrinkDecoder<R: Read> {
+    decoder: Decoder<BitSource<R>>,
+}
+
+impl<R: Read> ShrinkDecoder<R> {
+    pub fn new(reader: R) -> Self {
+        ShrinkDecoder {
+            decoder: Decoder::new(PARAMS, BitSource::new(reader, BitOrder::Lsb)),
+        }
+    }
+}
+
+impl<R: Read> Read for ShrinkDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.decoder.read(buf)
+    }
+}
+
+/// Сжимает срез в памяти методом Shrink
+pub fn shrink(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = ShrinkEncoder::new(Vec::new());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+/// Распаковывает данные записи ZIP, сжатые методом Shrink
+pub fn unshrink(data: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    ShrinkDecoder::new(data).read_to_end(&mut out)?;
+    Ok(out)
+}
@@ -0,0 +1,393 @@
+//! Потоковые адаптеры над `Compress` и `Decompress`
+use super::block::{read_block_header, read_index, IndexBlock, Point};
+use super::checksum::{Checksum, Hasher, Trailer};
+use super::error::{Error, Result};
+use super::header::Header;
+use super::{read_header, Compress, Decompress, DictionaryMode, Options};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+
+/// Кодировщик, реализующий `Write`: все записанные в него данные сжимаются
+/// и передаются во вложенный поток.
+///
+/// Заголовок архива записывается при создании. После записи всех данных
+/// необходимо вызвать `finish`, иначе последнее кодовое слово будет потеряно.
+pub struct LzwEncoder<W: Write> {
+    lzw_struct: Compress,
+    writer: BufWriter<W>,
+    // Контрольная сумма записанных данных, если она хранится в архиве
+    hasher: Option<Hasher>,
+}
+
+impl<W: Write> LzwEncoder<W> {
+    /// Создает кодировщик с заданным размером словаря и записывает заголовок архива
+    pub fn new(writer: W, max_bits_count: u8) -> Result<Self> {
+        LzwEncoder::with_mode(writer, max_bits_count, DictionaryMode::Reset)
+    }
+    /// Создает кодировщик с заданным размером словаря и режимом заполненного
+    /// словаря, записывает заголовок архива
+    pub fn with_mode(writer: W, max_bits_count: u8, mode: DictionaryMode) -> Result<Self> {
+        LzwEncoder::with_checksum(writer, max_bits_count, mode, None)
+    }
+    /// Создает кодировщик с заданными параметрами и записывает заголовок
+    /// архива. Шифрование и блочное сжатие потоковым кодировщиком
+    /// не поддерживаются
+    pub fn with_options(writer: W, options: &Options) -> Result<Self> {
+        if options.password.is_some() || options.blocks()?.is_some() {
+            return Err(Error::InvalidOption(
+                "потоковый кодировщик не поддерживает шифрование и блоки",
+            ));
+        }
+        LzwEncoder::with_header(writer, options.header()?)
+    }
+    /// Создает кодировщик, который в `finish` дописывает контрольную сумму
+    /// всех записанных данных, если задан ее алгоритм
+    pub fn with_checksum(
+        writer: W,
+        max_bits_count: u8,
+        mode: DictionaryMode,
+        checksum: Option<Checksum>,
+    ) -> Result<Self> {
+        let header = Header::new(max_bits_count, mode.flags()).with_checksum(checksum);
+        LzwEncoder::with_header(writer, header)
+    }
+    /// Создает кодировщик по заголовку архива и записывает его
+    pub(crate) fn with_header(writer: W, header: Header) -> Result<Self> {
+        let lzw_struct = Compress::new(&header)?;
+        let mut writer = BufWriter::new(writer);
+        header.write(&mut writer)?;
+        Ok(LzwEncoder {
+            lzw_struct,
+            writer,
+            hasher: header.checksum.map(Hasher::new),
+        })
+    }
+    /// Ссылка на вложенный поток
+    pub fn get_ref(&self) -> &W {
+        self.writer.get_ref()
+    }
+    /// Записывает оставшиеся в буфере биты и контрольную сумму,
+    /// возвращает вложенный поток
+    pub fn finish(mut self) -> Result<W> {
+        self.lzw_struct.last_bytes(&mut self.writer)?;
+        if let Some(hasher) = self.hasher.take() {
+            self.writer.write_all(&hasher.finish())?;
+        }
+        Ok(self.writer.into_inner().map_err(|err| err.into_error())?)
+    }
+}
+
+impl<W: Write> Write for LzwEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.lzw_struct.compress(buf, &mut self.writer)?;
+        if let Some(hasher) = self.hasher.as_mut() {
+            hasher.update(buf);
+        }
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Декодировщик, реализующий `Read`: читает сжатый архив из вложенного потока
+/// и отдает распакованные данные.
+///
+/// Заголовок архива считывается и проверяется при создании. Блоки
+/// блочного архива распаковываются по очереди, индекс блоков не считывается.
+/// Контрольная сумма, если она хранится в архиве, сверяется в конце данных:
+/// при несовпадении чтение возвращает ошибку.
+pub struct LzwDecoder<R: Read> {
+    lzw_struct: Decompress,
+    // Контрольная сумма обычного архива — последние байты потока, не коды
+    reader: BufReader<Trailer<R>>,
+    header: Header,
+    // Текущий блок блочного архива, None после завершающего блока
+    block: Option<Block>,
+    // Распакованное, но еще не отданное кодовое слово
+    buf: Vec<u8>,
+    // Позиция первого не отданного байта в buf
+    pos: usize,
+    // Контрольная сумма распакованных данных, None, если она не хранится
+    // в архиве или не может быть проверена после перемещения
+    hasher: Option<Hasher>,
+}
+
+impl<R: Read> LzwDecoder<R> {
+    /// Создает декодировщик, считывая заголовок архива из reader
+    pub fn new(mut reader: R) -> Result<Self> {
+        // Заголовок считывается без буферизации: до него неизвестно,
+        // сколько байт в конце потока занимает контрольная сумма
+        let header = read_header(&mut reader, false)?;
+        let trailer = match header.checksum {
+            Some(checksum) if !header.has_blocks() => checksum.output_len(),
+            _ => 0,
+        };
+        let mut reader = BufReader::new(Trailer::new(reader, trailer));
+        let block = if header.has_blocks() {
+            Block::next(&mut reader)?
+        } else {
+            // Обычный архив — один блок до конца потока
+            Some(Block {
+                size: None,
+                remaining: u64::MAX,
+                written: 0,
+            })
+        };
+        let mut decoder = LzwDecoder {
+            lzw_struct: Decompress::new(&header)?,
+            reader,
+            header,
+            block,
+            buf: Vec::new(),
+            pos: 0,
+            hasher: header.checksum.map(Hasher::new),
+        };
+        // В пустом блочном архиве за заголовком сразу следует завершающий блок
+        if decoder.block.is_none() {
+            decoder.verify_blocks()?;
+        }
+        Ok(decoder)
+    }
+    /// Заголовок распаковываемого архива
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    /// Ссылка на вложенный поток
+    pub fn get_ref(&self) -> &R {
+        self.reader.get_ref().get_ref()
+    }
+    /// Сверяет контрольную сумму, следующую за завершающим блоком
+    fn verify_blocks(&mut self) -> Result<()> {
+        if let (Some(hasher), Some(checksum)) = (self.hasher.take(), self.header.checksum) {
+            let mut expected = vec![0u8; checksum.output_len()];
+            self.reader.read_exact(&mut expected)?;
+            hasher.verify(&expected)?;
+        }
+        Ok(())
+    }
+    /// Декодирует очередное кодовое слово текущего блока в buf. Когда блок
+    /// заканчивается, проверяет его размер, переходит к следующему
+    /// и возвращает false
+    fn decode_next(&mut self) -> Result<bool> {
+        let block = match self.block.as_mut() {
+            Some(block) => block,
+            None => return Ok(false),
+        };
+        let start = self.buf.len();
+        let mut codes = (&mut self.reader).take(block.remaining);
+        let decoded = self.lzw_struct.decode_next(&mut codes, &mut self.buf)?;
+        block.remaining = codes.limit();
+        if decoded {
+            block.written += (self.buf.len() - start) as u64;
+            if block
+                .size
+                .is_some_and(|size| block.written > u64::from(size))
+            {
+                return Err(Error::BadHeader("неверный размер блока"));
+            }
+            if let Some(hasher) = self.hasher.as_mut() {
+                hasher.update(&self.buf[start..]);
+            }
+            return Ok(true);
+        }
+        match block.size {
+            None => {
+                self.block = None;
+                if let Some(hasher) = self.hasher.take() {
+                    hasher.verify(self.reader.get_ref().trailer()?)?;
+                }
+            }
+            Some(size) => {
+                if block.remaining != 0 || block.written != u64::from(size) {
+                    return Err(Error::BadHeader("неверный размер блока"));
+                }
+                self.block = Block::next(&mut self.reader)?;
+                // Контрольная сумма следует за завершающим блоком
+                if self.block.is_none() {
+                    self.verify_blocks()?;
+                }
+                self.lzw_struct = Decompress::new(&self.header)?;
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl<R: Read + Seek> LzwDecoder<R> {
+    /// Переходит к точке входа блока: дальше распаковка продолжается с нее
+    fn jump(&mut self, block: &IndexBlock, point: &Point) -> Result<()> {
+        let start = point.bit_offset / 8;
+        self.reader.seek(SeekFrom::Start(start))?;
+        let mut lzw_struct = Decompress::new(&self.header)?;
+        lzw_struct.carried = point.carried.clone();
+        // Точка входа может начинаться с середины байта
+        let mut codes = (&mut self.reader).take(block.end - start);
+        let skip = (point.bit_offset % 8) as u8;
+        if skip != 0 {
+            lzw_struct
+                .bits
+                .read_code(&mut codes, skip)?
+                .ok_or(Error::Truncated)?;
+        }
+        self.block = Some(Block {
+            size: Some(block.size as u32),
+            remaining: codes.limit(),
+            written: point.offset - block.offset,
+        });
+        self.lzw_struct = lzw_struct;
+        self.buf.clear();
+        self.pos = 0;
+        // Пропущенные данные не учтены в контрольной сумме
+        self.hasher = None;
+        Ok(())
+    }
+}
+
+/// Распаковываемый блок
+struct Block {
+    // Исходный размер, None для обычного архива
+    size: Option<u32>,
+    // Оставшиеся байты кодов и уже распакованные байты
+    remaining: u64,
+    written: u64,
+}
+
+impl Block {
+    /// Считывает заголовок следующего блока блочного архива
+    fn next<R: Read>(reader: &mut R) -> Result<Option<Block>> {
+        Ok(read_block_header(reader)?.map(|(size, len)| Block {
+            size: Some(size),
+            remaining: u64::from(len),
+            written: 0,
+        }))
+    }
+}
+
+impl<R: Read> Read for LzwDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.buf.len() {
+            self.buf.clear();
+            self.pos = 0;
+            // Каждое кодовое слово дает хотя бы один байт
+            while !self.decode_next()? {
+                if self.block.is_none() {
+                    return Ok(0);
+                }
+            }
+        }
+        let len = buf.len().min(self.buf.len() - self.pos);
+        buf[..len].copy_from_slice(&self.buf[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+/// Декодировщик блочного архива с произвольным доступом: реализует `Read`
+/// и `Seek` по распакованным данным.
+///
+/// При создании считывается индекс блоков. Чтобы прочитать данные с заданной
+/// позиции, распаковка начинается с ближайшей предшествующей точки входа:
+/// начала блока или сброса словаря внутри него.
+///
+/// Перемещение за конец данных допустимо, как и в `File`: `seek` возвращает
+/// запрошенную позицию, а `read` с нее отдает 0 байт. Перемещение до начала
+/// данных — ошибка `InvalidInput`.
+pub struct SeekableDecoder<R: Read + Seek> {
+    decoder: LzwDecoder<R>,
+    index: Vec<IndexBlock>,
+    // Размер распакованных данных
+    len: u64,
+    // Позиция чтения и позиция, с которой продолжит декодировщик
+    pos: u64,
+    decoded: u64,
+}
+
+impl<R: Read + Seek> SeekableDecoder<R> {
+    /// Создает декодировщик, считывая заголовок и индекс блочного архива
+    pub fn new(mut reader: R) -> Result<Self> {
+        let header = read_header(&mut reader, false)?;
+        if !header.has_blocks() {
+            return Err(Error::BadHeader("архив не блочный, индекса блоков нет"));
+        }
+        let index = read_index(&mut reader, &header)?;
+        let len = index.last().map_or(0, |block| block.offset + block.size);
+        reader.seek(SeekFrom::Start(0))?;
+        Ok(SeekableDecoder {
+            decoder: LzwDecoder::new(reader)?,
+            index,
+            len,
+            pos: 0,
+            decoded: 0,
+        })
+    }
+    /// Размер распакованных данных
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+    /// Пустой ли архив
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    /// Заголовок распаковываемого архива
+    pub fn header(&self) -> &Header {
+        self.decoder.header()
+    }
+    /// Переходит к ближайшей точке входа перед текущей позицией, если
+    /// декодировщику до нее дальше, чем от точки входа
+    fn jump(&mut self) -> Result<()> {
+        let pos = self.pos;
+        let block = match self.index.binary_search_by_key(&pos, |block| block.offset) {
+            Ok(i) => &self.index[i],
+            Err(i) => &self.index[i - 1],
+        };
+        let point = match block
+            .points
+            .binary_search_by_key(&pos, |point| point.offset)
+        {
+            Ok(i) => &block.points[i],
+            Err(i) => &block.points[i - 1],
+        };
+        if point.offset <= self.decoded && self.decoded <= pos {
+            return Ok(());
+        }
+        self.decoder.jump(block, point)?;
+        self.decoded = point.offset;
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Read for SeekableDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+        self.jump()?;
+        // Пропускаем данные от точки входа до позиции чтения
+        let mut skip = [0u8; 4096];
+        while self.decoded < self.pos {
+            let len = skip.len().min((self.pos - self.decoded) as usize);
+            match self.decoder.read(&mut skip[..len])? {
+                0 => return Err(Error::Truncated.into()),
+                len => self.decoded += len as u64,
+            }
+        }
+        let len = self.decoder.read(buf)?;
+        self.decoded += len as u64;
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+impl<R: Read + Seek> Seek for SeekableDecoder<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        };
+        self.pos = pos.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "перемещение до начала данных")
+        })?;
+        Ok(self.pos)
+    }
+}
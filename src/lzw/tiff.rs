@@ -0,0 +1,132 @@
+//! LZW в варианте TIFF и фильтра `LZWDecode` в PDF
+//!
+//! Коды записываются старшим битом вперед, как и в основном формате,
+//! начиная с 9 бит и до 12; словарь строк у них тоже общий. Код 256
+//! сбрасывает словарь (Clear), код 257 завершает поток (EOD), новые строки
+//! получают коды с 258. При раннем переходе (`EarlyChange`) разрядность
+//! растет на один код раньше, чем это строго необходимо: так пишет libtiff,
+//! и так по умолчанию читает PDF.
+use super::bits::BitOrder;
+use super::codec::{BitSink, BitSource, Decoder, Encoder, Full, Params};
+use super::error::Result;
+use std::io::{self, Read, Write};
+
+/// Код сброса словаря
+const CLEAR: u32 = 256;
+/// Код конца данных
+const EOD: u32 = 257;
+/// Начальная разрядность кодов
+const INIT_BITS: u8 = 9;
+/// Наибольшая разрядность кодов
+pub const MAX_BITS: u8 = 12;
+/// Размер словаря
+const TABLE_SIZE: u32 = 1 << MAX_BITS;
+
+/// Параметры кодека
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// Ранний переход к следующей разрядности. Параметр `EarlyChange`
+    /// фильтра `LZWDecode`; в TIFF всегда включен
+    pub early_change: bool,
+}
+
+impl Config {
+    /// Сжатие LZW в TIFF. Так же по умолчанию (`EarlyChange` 1)
+    /// работает фильтр `LZWDecode` в PDF
+    pub const TIFF: Config = Config { early_change: true };
+    /// Фильтр `LZWDecode` в PDF с `EarlyChange` 0
+    pub const LATE_CHANGE: Config = Config {
+        early_change: false,
+    };
+    /// Параметры ядра LZW
+    fn params(self) -> Params {
+        Params {
+            literals: 256,
+            clear: Some(CLEAR),
+            end: Some(EOD),
+            end_required: false,
+            control: None,
+            init_bits: INIT_BITS,
+            max_bits: MAX_BITS,
+            table_size: TABLE_SIZE,
+            // Словарь заполнен с запасом, который нужен декодеру
+            // с ранним переходом, чтобы не выйти за 12 бит
+            spare: 2 + self.early_change as u32,
+            early_change: self.early_change,
+            full: Full::Clear,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config::TIFF
+    }
+}
+
+/// Кодировщик TIFF/PDF LZW, реализующий `Write`. Поток начинается с Clear;
+/// при заполнении словаря выдается Clear и словарь начинается заново.
+///
+/// После записи всех данных необходимо вызвать `finish`, который допишет EOD.
+pub struct TiffEncoder<W: Write> {
+    encoder: Encoder<BitSink<W>>,
+}
+
+impl<W: Write> TiffEncoder<W> {
+    /// Создает кодировщик с заданными параметрами
+    pub fn new(writer: W, config: Config) -> Result<Self> {
+        let mut encoder = Encoder::new(config.params(), BitSink::new(writer, BitOrder::Msb));
+        encoder.clear()?;
+        Ok(TiffEncoder { encoder })
+    }
+    /// Записывает последний код, EOD и последний байт, дополняя его нулями,
+    /// и возвращает вложенный поток
+    pub fn finish(self) -> Result<W> {
+        self.encoder.finish()?.finish()
+    }
+}
+
+impl<W: Write> Write for TiffEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.encoder.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder.flush()
+    }
+}
+
+/// Декодировщик TIFF/PDF LZW, реализующий `Read`: отдает данные до кода EOD.
+///
+/// Поток, оборванный без EOD, считается завершенным: такие полосы TIFF
+/// и потоки PDF встречаются на практике, и libtiff их тоже принимает.
+pub struct TiffDecoder<R: Read> {
+    decoder: Decoder<BitSource<R>>,
+}
+
+impl<R: Read> TiffDecoder<R> {
+    /// Создает декодировщик с заданными параметрами
+    pub fn new(reader: R, config: Config) -> Self {
+        TiffDecoder {
+            decoder: Decoder::new(config.params(), BitSource::new(reader, BitOrder::Msb)),
+        }
+    }
+}
+
+impl<R: Read> Read for TiffDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.decoder.read(buf)
+    }
+}
+
+/// Сжимает срез в памяти, например полосу изображения TIFF
+pub fn encode(data: &[u8], config: Config) -> Result<Vec<u8>> {
+    let mut encoder = TiffEncoder::new(Vec::new(), config)?;
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+/// Распаковывает срез в памяти, например содержимое потока PDF с фильтром `LZWDecode`
+pub fn decode(data: &[u8], config: Config) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    TiffDecoder::new(data, config).read_to_end(&mut out)?;
+    Ok(out)
+}
@@ -0,0 +1,219 @@
+use lzw_arc::lzw::archive::{ArchiveReader, ArchiveWriter, EntryKind};
+use lzw_arc::lzw::{self, Error};
+use std::fs::{self, File};
+use std::io::Cursor;
+
+fn options(password: Option<&str>) -> lzw::Options {
+    lzw::Options {
+        max_bits_count: 12,
+        password: password.map(String::from),
+        kdf_iterations: 1000,
+        ..Default::default()
+    }
+}
+fn build(password: Option<&str>) -> Vec<u8> {
+    let mut writer = ArchiveWriter::new(Vec::new(), &options(password)).unwrap();
+    writer.add("a.txt", &b"abracadabra"[..]).unwrap();
+    writer.add("dir/empty", &b""[..]).unwrap();
+    writer
+        .add("test-file", File::open("test-file").unwrap())
+        .unwrap();
+    writer.finish().unwrap()
+}
+
+#[test]
+fn archive_round_trip_test() {
+    let mut reader = ArchiveReader::new(Cursor::new(build(None))).unwrap();
+    let names: Vec<&str> = reader.entries().iter().map(|e| e.name.as_str()).collect();
+    assert_eq!(names, ["a.txt", "dir/empty", "test-file"]);
+    let expected = [
+        b"abracadabra".to_vec(),
+        Vec::new(),
+        fs::read("test-file").unwrap(),
+    ];
+    for (index, data) in expected.iter().enumerate() {
+        assert_eq!(reader.entries()[index].size, data.len() as u64);
+        assert_eq!(reader.header(index).unwrap().max_bits_count, 12);
+        let mut out = Vec::new();
+        reader.extract(index, &mut out).unwrap();
+        assert!(&out == data);
+    }
+}
+#[test]
+fn archive_aes_test() {
+    let archive = build(Some("secret"));
+    let mut reader = ArchiveReader::new(Cursor::new(&archive)).unwrap();
+    match reader.extract(0, &mut Vec::new()) {
+        Err(Error::PasswordRequired) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+    reader.set_password("wrong");
+    match reader.extract_all("archive_aes_test") {
+        Err(Error::WrongPassword) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+    // Ни одного файла не создано
+    assert!(!std::path::Path::new("archive_aes_test").exists());
+    reader.set_password("secret");
+    // Все записи зашифрованы с одной солью
+    assert_eq!(reader.header(0).unwrap().kdf, reader.header(2).unwrap().kdf);
+    let mut out = Vec::new();
+    reader.extract(2, &mut out).unwrap();
+    assert!(out == fs::read("test-file").unwrap());
+}
+#[test]
+fn archive_directory_test() {
+    fs::create_dir_all("archive_test_src/sub").unwrap();
+    fs::write("archive_test_src/one", b"one one one").unwrap();
+    fs::write("archive_test_src/sub/two", b"two two two").unwrap();
+    let mut writer = ArchiveWriter::new(Vec::new(), &options(None)).unwrap();
+    writer.add_path("archive_test_src").unwrap();
+    writer.add_path("test-file").unwrap();
+    let archive = writer.finish().unwrap();
+
+    let mut reader = ArchiveReader::new(Cursor::new(archive)).unwrap();
+    reader.extract_all("archive_test_dst").unwrap();
+    let one = fs::read("archive_test_dst/archive_test_src/one").unwrap();
+    let two = fs::read("archive_test_dst/archive_test_src/sub/two").unwrap();
+    let source = fs::read("archive_test_dst/test-file").unwrap();
+    fs::remove_dir_all("archive_test_src").unwrap();
+    fs::remove_dir_all("archive_test_dst").unwrap();
+    assert_eq!(one, b"one one one");
+    assert_eq!(two, b"two two two");
+    assert!(source == fs::read("test-file").unwrap());
+}
+#[test]
+fn archive_errors_test() {
+    let mut writer = ArchiveWriter::new(Vec::new(), &options(None)).unwrap();
+    for name in &["../evil", "/etc/passwd", "a//b", ""] {
+        match writer.add(name, &b"data"[..]) {
+            Err(Error::InvalidEntryName(_)) => {}
+            result => panic!("unexpected result for {:?}: {:?}", name, result),
+        }
+    }
+    writer.add("data", &b"data"[..]).unwrap();
+    match writer.add("data", &b"data"[..]) {
+        Err(Error::InvalidEntryName(_)) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+    // Не многофайловый архив
+    let single = lzw::compress_bytes(b"data", 9).unwrap();
+    match ArchiveReader::new(Cursor::new(single)) {
+        Err(Error::BadHeader(_)) => {}
+        result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+    // CRC32 последней записи хранится прямо перед концевиком
+    let mut archive = build(None);
+    let crc = archive.len() - 12 - 1;
+    archive[crc] ^= 0x01;
+    let mut reader = ArchiveReader::new(Cursor::new(archive)).unwrap();
+    match reader.extract(2, &mut Vec::new()) {
+        Err(Error::ChecksumMismatch) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+    // Номер за пределами оглавления
+    let count = reader.entries().len();
+    match reader.extract(count, &mut Vec::new()) {
+        Err(Error::NoSuchEntry(index)) => assert_eq!(index, count),
+        result => panic!("unexpected result: {:?}", result),
+    }
+    match reader.header(usize::MAX) {
+        Err(Error::NoSuchEntry(_)) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+    assert!(reader.entry(count).is_err());
+}
+#[cfg(unix)]
+#[test]
+fn archive_metadata_test() {
+    use filetime::FileTime;
+    use std::os::unix::fs::{symlink, PermissionsExt};
+    let mtime = FileTime::from_unix_time(1_000_000_000, 0);
+    fs::create_dir_all("metadata_test_src/empty").unwrap();
+    fs::write("metadata_test_src/script", b"#!/bin/sh").unwrap();
+    fs::set_permissions(
+        "metadata_test_src/script",
+        fs::Permissions::from_mode(0o4750),
+    )
+    .unwrap();
+    filetime::set_file_mtime("metadata_test_src/script", mtime).unwrap();
+    symlink("script", "metadata_test_src/link").unwrap();
+    filetime::set_file_mtime("metadata_test_src/empty", mtime).unwrap();
+    let mut writer = ArchiveWriter::new(Vec::new(), &options(None)).unwrap();
+    writer.add_path("metadata_test_src").unwrap();
+    let archive = writer.finish().unwrap();
+
+    let mut reader = ArchiveReader::new(Cursor::new(archive)).unwrap();
+    let kinds: Vec<_> = reader
+        .entries()
+        .iter()
+        .map(|e| (e.name.as_str(), e.kind))
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            ("metadata_test_src", EntryKind::Directory),
+            ("metadata_test_src/empty", EntryKind::Directory),
+            ("metadata_test_src/link", EntryKind::Symlink),
+            ("metadata_test_src/script", EntryKind::File),
+        ]
+    );
+    reader.extract_all("metadata_test_dst").unwrap();
+    let dst = std::path::Path::new("metadata_test_dst/metadata_test_src");
+    let script = fs::metadata(dst.join("script")).unwrap();
+    let empty = fs::metadata(dst.join("empty")).unwrap();
+    let link = fs::read_link(dst.join("link")).unwrap();
+    // Бит setuid восстанавливается только по явному запросу
+    reader.set_special_bits(true);
+    reader.extract_all("metadata_test_dst").unwrap();
+    let special = fs::metadata(dst.join("script")).unwrap();
+    fs::remove_dir_all("metadata_test_src").unwrap();
+    fs::remove_dir_all("metadata_test_dst").unwrap();
+    assert_eq!(script.permissions().mode() & 0o7777, 0o750);
+    assert_eq!(special.permissions().mode() & 0o7777, 0o4750);
+    assert_eq!(FileTime::from_last_modification_time(&script), mtime);
+    assert!(empty.is_dir());
+    assert_eq!(FileTime::from_last_modification_time(&empty), mtime);
+    assert_eq!(link, std::path::Path::new("script"));
+}
+#[cfg(unix)]
+#[test]
+fn archive_symlink_escape_test() {
+    use std::os::unix::fs::symlink;
+    // Архив каталога symlink_test_src с единственной ссылкой inner/link
+    let with_link = |target: &str| {
+        fs::create_dir_all("symlink_test_src/inner").unwrap();
+        symlink(target, "symlink_test_src/inner/link").unwrap();
+        let mut writer = ArchiveWriter::new(Vec::new(), &options(None)).unwrap();
+        writer.add_path("symlink_test_src").unwrap();
+        fs::remove_dir_all("symlink_test_src").unwrap();
+        writer.finish().unwrap()
+    };
+    let escaping = with_link("../../../outside");
+    let absolute = with_link("/tmp");
+    let inside = with_link("../inner");
+    // Следующая распаковка пишет по пути, проходящему через ссылку из предыдущей
+    let mut writer = ArchiveWriter::new(Vec::new(), &options(None)).unwrap();
+    writer
+        .add("symlink_test_src/inner/link/file", &b"data"[..])
+        .unwrap();
+    let through = writer.finish().unwrap();
+
+    let mut results = Vec::new();
+    for archive in [escaping, absolute, inside, through] {
+        let mut reader = ArchiveReader::new(Cursor::new(archive)).unwrap();
+        results.push(reader.extract_all("symlink_test_dst"));
+    }
+    let link = fs::read_link("symlink_test_dst/symlink_test_src/inner/link");
+    let extracted = fs::read_dir("symlink_test_dst/symlink_test_src/inner").map(Iterator::count);
+    fs::remove_dir_all("symlink_test_dst").unwrap();
+    for &index in &[0, 1, 3] {
+        match &results[index] {
+            Err(Error::UnsafeSymlink(_)) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+    assert!(results[2].is_ok());
+    assert_eq!(link.unwrap(), std::path::Path::new("../inner"));
+    assert_eq!(extracted.unwrap(), 1);
+}
@@ -0,0 +1,204 @@
+mod common;
+
+use common::compress_with;
+use lzw_arc::lzw::archive::{ArchiveReader, ArchiveWriter};
+use lzw_arc::lzw::block::{MAGIC, MAX_THREADS};
+use lzw_arc::lzw::header::{Header, FLAG_BLOCKS};
+use lzw_arc::lzw::{self, DictionaryMode, Error, LzwDecoder};
+use std::fs::read;
+use std::io::{Cursor, Read};
+
+fn blocks(block_size: usize, threads: usize) -> lzw::Options {
+    lzw::Options {
+        max_bits_count: 12,
+        threads,
+        block_size: Some(block_size),
+        ..Default::default()
+    }
+}
+fn read_u32(data: &[u8]) -> u32 {
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(&data[..4]);
+    u32::from_le_bytes(buf)
+}
+fn read_u64(data: &[u8]) -> u64 {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(&data[..8]);
+    u64::from_le_bytes(buf)
+}
+
+#[test]
+fn block_round_trip_test() {
+    let source = read("test-file").unwrap();
+    let single = compress_with(&source, &blocks(100_000, 1));
+    let header = Header::read(&mut &single[..]).unwrap();
+    assert_eq!(header.flags & FLAG_BLOCKS, FLAG_BLOCKS);
+    // Архив не зависит от количества потоков
+    for threads in 2..=4 {
+        assert!(compress_with(&source, &blocks(100_000, threads)) == single);
+    }
+    assert!(lzw::decompress_bytes(&single).unwrap() == source);
+    for threads in 1..=4 {
+        let mut result = Vec::new();
+        lzw::decompress_parallel(single.as_slice(), &mut result, threads).unwrap();
+        assert!(result == source);
+    }
+    // Режимы словаря и размер блока не кратный входу
+    for &mode in &[DictionaryMode::Lzt, DictionaryMode::Clear] {
+        let options = lzw::Options {
+            dictionary_mode: mode,
+            ..blocks(77_777, 3)
+        };
+        let compressed = compress_with(&source, &options);
+        assert!(lzw::decompress_bytes(&compressed).unwrap() == source);
+    }
+    // Пустой поток и блоки по одному байту
+    let compressed = compress_with(&[], &blocks(1000, 2));
+    assert!(lzw::decompress_bytes(&compressed).unwrap().is_empty());
+    let compressed = compress_with(&source[..1000], &blocks(1, 2));
+    assert!(lzw::decompress_bytes(&compressed).unwrap() == source[..1000]);
+    // Несколько потоков без размера блока: блоки по умолчанию
+    let options = lzw::Options {
+        threads: 2,
+        ..Default::default()
+    };
+    let compressed = compress_with(&source, &options);
+    assert!(Header::read(&mut &compressed[..]).unwrap().has_blocks());
+    assert!(lzw::decompress_bytes(&compressed).unwrap() == source);
+}
+#[test]
+fn block_index_test() {
+    let source = read("test-file").unwrap();
+    let compressed = compress_with(&source, &blocks(100_000, 2));
+    let footer = &compressed[compressed.len() - 12..];
+    assert_eq!(footer[8..], MAGIC);
+    let index_offset = read_u64(footer) as usize;
+    let count = read_u32(&compressed[index_offset..]) as usize;
+    assert_eq!(count, source.len().div_ceil(100_000));
+    let mut entry = &compressed[index_offset + 4..];
+    let mut resets = 0;
+    for block in 0..count {
+        assert_eq!(read_u64(entry), block as u64 * 100_000);
+        // Первый код блока начинается сразу за его заголовком
+        let bit_offset = read_u64(&entry[8..]);
+        assert_eq!(bit_offset % 8, 0);
+        let size = read_u32(&compressed[bit_offset as usize / 8 - 8..]) as usize;
+        let expected = 100_000.min(source.len() - block * 100_000);
+        assert_eq!(size, expected);
+        // Точки входа после сброса словаря внутри блока
+        let points = read_u32(&entry[16..]);
+        entry = &entry[20..];
+        let mut last = (block as u64 * 100_000, bit_offset);
+        for _ in 0..points {
+            let point = (read_u64(entry), read_u64(&entry[8..]));
+            assert!(point.0 > last.0 && point.0 < (block * 100_000 + size) as u64);
+            assert!(point.1 > last.1);
+            last = point;
+            // Сброшенный словарь 12 бит оставляет непустое кодовое слово
+            let carried = read_u32(&entry[16..]) as usize;
+            assert!(carried > 0);
+            entry = &entry[20 + carried..];
+            resets += 1;
+        }
+    }
+    assert!(resets > count);
+    assert_eq!(entry.len(), 12);
+}
+#[test]
+fn block_stream_test() {
+    let source = &read("test-file").unwrap()[..300_000];
+    let compressed = compress_with(source, &blocks(10_000, 4));
+    // Потоковый декодер читает блоки по очереди
+    let mut decoder = LzwDecoder::new(compressed.as_slice()).unwrap();
+    let mut result = Vec::new();
+    let mut buf = [0u8; 777];
+    loop {
+        let len = decoder.read(&mut buf).unwrap();
+        if len == 0 {
+            break;
+        }
+        result.extend_from_slice(&buf[..len]);
+    }
+    assert!(result == source);
+    // Многофайловый архив
+    let mut writer = ArchiveWriter::new(Vec::new(), &blocks(10_000, 3)).unwrap();
+    writer.add("one", source).unwrap();
+    writer.add("two", &source[..5]).unwrap();
+    let archive = writer.finish().unwrap();
+    let mut reader = ArchiveReader::new(Cursor::new(archive)).unwrap();
+    assert!(reader.header(0).unwrap().has_blocks());
+    reader.set_threads(2);
+    let mut result = Vec::new();
+    reader.extract(0, &mut result).unwrap();
+    assert!(result == source);
+}
+#[test]
+fn block_error_test() {
+    let source = &read("test-file").unwrap()[..100_000];
+    let mut compressed = Vec::new();
+    // Шифрование и блоки несовместимы
+    let options = lzw::Options {
+        password: Some("secret".to_owned()),
+        kdf_iterations: 1000,
+        ..blocks(10_000, 2)
+    };
+    match lzw::compress_bytes_with(source, &options, &mut compressed) {
+        Err(Error::InvalidOption(_)) => {}
+        result => panic!("{:?}", result),
+    }
+    for options in &[
+        blocks(0, 1),
+        blocks(10_000, 0),
+        blocks(10_000, MAX_THREADS + 1),
+    ] {
+        match lzw::compress_bytes_with(source, options, &mut compressed) {
+            Err(Error::InvalidOption(_)) => {}
+            result => panic!("{:?}", result),
+        }
+    }
+    // Оборванный архив
+    let compressed = compress_with(source, &blocks(10_000, 2));
+    for &len in &[100, 20_000] {
+        match lzw::decompress_bytes(&compressed[..len]) {
+            Err(Error::Truncated) => {}
+            result => panic!("{:?}", result.map(|data| data.len())),
+        }
+    }
+    for &threads in &[0, MAX_THREADS + 1] {
+        match lzw::decompress_parallel(compressed.as_slice(), &mut Vec::new(), threads) {
+            Err(Error::InvalidOption(_)) => {}
+            result => panic!("{:?}", result),
+        }
+    }
+    // Неверный исходный размер блока
+    let mut corrupted = compressed.clone();
+    corrupted[7] ^= 1;
+    assert!(lzw::decompress_bytes(&corrupted).is_err());
+    let mut result = Vec::new();
+    assert!(LzwDecoder::new(corrupted.as_slice())
+        .unwrap()
+        .read_to_end(&mut result)
+        .is_err());
+    // Распаковка блока прерывается, как только данных больше заявленного
+    let mut corrupted = compressed.clone();
+    corrupted[7..11].copy_from_slice(&1u32.to_le_bytes());
+    match lzw::decompress_parallel(corrupted.as_slice(), &mut Vec::new(), 2) {
+        Err(Error::BadHeader(_)) => {}
+        result => panic!("{:?}", result),
+    }
+    let mut decoder = LzwDecoder::new(corrupted.as_slice()).unwrap();
+    let mut result = Vec::new();
+    assert!(decoder.read_to_end(&mut result).is_err());
+    assert!(result.len() <= 1);
+    // Размер больше наибольшего отвергается сразу
+    let mut corrupted = compressed.clone();
+    corrupted[7..11].copy_from_slice(&u32::MAX.to_le_bytes());
+    match lzw::decompress_bytes(&corrupted) {
+        Err(Error::BadHeader(_)) => {}
+        result => panic!("{:?}", result.map(|data| data.len())),
+    }
+    match lzw::decompress_parallel(corrupted.as_slice(), &mut Vec::new(), 2) {
+        Err(Error::BadHeader(_)) => {}
+        result => panic!("{:?}", result),
+    }
+}
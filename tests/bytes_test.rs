@@ -0,0 +1,123 @@
+use lzw_arc::lzw;
+use std::fs::read;
+
+#[test]
+fn compress_bytes_test() {
+    let source = read("test-file").unwrap();
+    let compressed = lzw::compress_bytes(&source, 16).unwrap();
+    assert!(compressed == read("test-compressed").unwrap());
+    assert!(lzw::decompress_bytes(&compressed).unwrap() == source);
+}
+#[test]
+fn aes_bytes_test() {
+    let source = read("test-file").unwrap();
+    let compressed = lzw::compress_aes_bytes(&source, 16, "secret").unwrap();
+    assert!(lzw::decompress_aes_bytes(&compressed, "secret").unwrap() == source);
+}
+#[test]
+fn reuse_buffer_test() {
+    let mut compressed = Vec::new();
+    let mut result = Vec::new();
+    for data in &[&b"abracadabra"[..], b"mississippi", b"banana bandana"] {
+        compressed.clear();
+        result.clear();
+        lzw::compress_bytes_into(data, 9, &mut compressed).unwrap();
+        lzw::decompress_bytes_into(&compressed, &mut result).unwrap();
+        assert_eq!(&result[..], *data);
+    }
+}
+#[test]
+fn bytes_match_stream_test() {
+    // Срез в памяти сжимается в обход буферизации, но в тот же архив
+    let source = &read("test-file").unwrap()[..100_000];
+    for &mode in &[lzw::DictionaryMode::Reset, lzw::DictionaryMode::Clear] {
+        for data in &[source, &source[..10], &[]] {
+            let options = lzw::Options {
+                max_bits_count: 12,
+                dictionary_mode: mode,
+                checksum: Some(lzw::Checksum::Crc32),
+                ..Default::default()
+            };
+            let mut compressed = Vec::new();
+            lzw::compress_bytes_with(data, &options, &mut compressed).unwrap();
+            let mut streamed = Vec::new();
+            lzw::compress_stream(*data, &mut streamed, &options).unwrap();
+            assert!(compressed == streamed);
+            assert!(lzw::decompress_bytes(&compressed).unwrap() == *data);
+        }
+    }
+}
+#[test]
+fn small_dictionary_test() {
+    // Маленький словарь многократно сбрасывается
+    let source = &read("test-file").unwrap()[..200_000];
+    for bits in 9..=13 {
+        let compressed = lzw::compress_bytes(source, bits).unwrap();
+        assert!(lzw::decompress_bytes(&compressed).unwrap() == source);
+    }
+    // Пустой поток
+    let compressed = lzw::compress_bytes(&[], 9).unwrap();
+    assert!(lzw::decompress_bytes(&compressed).unwrap().is_empty());
+}
+#[test]
+fn aes_trailing_zeros_test() {
+    // Сжатые данные, оканчивающиеся нулевыми байтами, в том числе целый
+    // нулевой последний блок, не должны теряться при снятии дополнения
+    for len in 0..40 {
+        let source = vec![0u8; len];
+        let compressed = lzw::compress_aes_bytes(&source, 9, "secret").unwrap();
+        assert_eq!(
+            lzw::decompress_aes_bytes(&compressed, "secret").unwrap(),
+            source
+        );
+    }
+    // Сжатый при 10 битах test-file оканчивается нулевым байтом
+    let source = read("test-file").unwrap();
+    let compressed = lzw::compress_bytes(&source, 10).unwrap();
+    assert_eq!(compressed.last(), Some(&0));
+    let compressed = lzw::compress_aes_bytes(&source, 10, "secret").unwrap();
+    assert!(lzw::decompress_aes_bytes(&compressed, "secret").unwrap() == source);
+}
+#[test]
+fn kdf_params_test() {
+    let options = lzw::Options {
+        max_bits_count: 12,
+        password: Some("secret".to_owned()),
+        kdf_iterations: 1000,
+        ..Default::default()
+    };
+    let mut first = Vec::new();
+    let mut second = Vec::new();
+    lzw::compress_bytes_with(b"abracadabra", &options, &mut first).unwrap();
+    lzw::compress_bytes_with(b"abracadabra", &options, &mut second).unwrap();
+    // Количество итераций записано в заголовок, соль у каждого архива своя
+    let first_kdf = lzw::header::Header::read(&mut &first[..])
+        .unwrap()
+        .kdf
+        .unwrap();
+    let second_kdf = lzw::header::Header::read(&mut &second[..])
+        .unwrap()
+        .kdf
+        .unwrap();
+    assert_eq!(first_kdf.iterations, 1000);
+    assert!(first_kdf.salt != second_kdf.salt);
+    // Параметры для распаковки берутся из заголовка
+    assert_eq!(
+        lzw::decompress_aes_bytes(&first, "secret").unwrap(),
+        b"abracadabra"
+    );
+    assert_eq!(
+        lzw::decompress_aes_bytes(&second, "secret").unwrap(),
+        b"abracadabra"
+    );
+}
+#[test]
+fn large_dictionary_test() {
+    // Словарь размером 24 и 32 бита не заполняется, память расходуется
+    // только на добавленные строки
+    let source = read("test-file").unwrap();
+    for &bits in &[24, 32] {
+        let compressed = lzw::compress_bytes(&source, bits).unwrap();
+        assert!(lzw::decompress_bytes(&compressed).unwrap() == source);
+    }
+}
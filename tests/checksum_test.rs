@@ -0,0 +1,164 @@
+mod common;
+
+use common::{compress_with, decode_stream};
+use lzw_arc::lzw::archive::{ArchiveReader, ArchiveWriter};
+use lzw_arc::lzw::header::{Header, FLAG_CHECKSUM};
+use lzw_arc::lzw::{self, Checksum, Error, LzwEncoder, SeekableDecoder};
+use std::fs::read;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+const CHECKSUMS: [Checksum; 3] = [Checksum::Crc32, Checksum::XxHash64, Checksum::Sha256];
+
+fn with_checksum(checksum: Checksum) -> lzw::Options {
+    lzw::Options {
+        max_bits_count: 12,
+        checksum: Some(checksum),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn checksum_round_trip_test() {
+    let source = &read("test-file").unwrap()[..200_000];
+    let plain = lzw::compress_bytes(source, 12).unwrap();
+    for &checksum in &CHECKSUMS {
+        let options = with_checksum(checksum);
+        let compressed = compress_with(source, &options);
+        let header = Header::read(&mut &compressed[..]).unwrap();
+        assert_eq!(header.flags & FLAG_CHECKSUM, FLAG_CHECKSUM);
+        assert_eq!(header.checksum, Some(checksum));
+        // Заголовок длиннее на байт алгоритма, в конце — контрольная сумма
+        assert_eq!(compressed.len(), plain.len() + 1 + checksum.output_len());
+        assert!(lzw::decompress_bytes(&compressed).unwrap() == source);
+        assert!(decode_stream(&compressed).unwrap() == source);
+        let mut result = Vec::new();
+        lzw::decompress_parallel(compressed.as_slice(), &mut result, 2).unwrap();
+        assert!(result == source);
+        // Потоковый кодировщик дает тот же архив
+        let mut encoder =
+            LzwEncoder::with_checksum(Vec::new(), 12, Default::default(), Some(checksum)).unwrap();
+        for chunk in source.chunks(777) {
+            encoder.write_all(chunk).unwrap();
+        }
+        assert!(encoder.finish().unwrap() == compressed);
+        // Шифрование
+        let options = lzw::Options {
+            password: Some("secret".to_owned()),
+            kdf_iterations: 1000,
+            ..with_checksum(checksum)
+        };
+        let compressed = compress_with(source, &options);
+        assert!(lzw::decompress_aes_bytes(&compressed, "secret").unwrap() == source);
+        // Блоки
+        let options = lzw::Options {
+            block_size: Some(30_000),
+            threads: 2,
+            ..with_checksum(checksum)
+        };
+        let compressed = compress_with(source, &options);
+        assert!(lzw::decompress_bytes(&compressed).unwrap() == source);
+        assert!(decode_stream(&compressed).unwrap() == source);
+        let mut decoder = SeekableDecoder::new(Cursor::new(compressed)).unwrap();
+        let mut buf = [0u8; 100];
+        decoder.seek(SeekFrom::Start(150_000)).unwrap();
+        decoder.read_exact(&mut buf).unwrap();
+        assert!(buf[..] == source[150_000..150_100]);
+    }
+    // Пустой поток, в том числе блочный
+    for &checksum in &CHECKSUMS {
+        for &block_size in &[None, Some(1000)] {
+            let options = lzw::Options {
+                block_size,
+                ..with_checksum(checksum)
+            };
+            let mut compressed = compress_with(&[], &options);
+            assert!(lzw::decompress_bytes(&compressed).unwrap().is_empty());
+            assert!(decode_stream(&compressed).unwrap().is_empty());
+            let position = compressed.len() - if block_size.is_some() { 17 } else { 1 };
+            compressed[position] ^= 1;
+            assert!(decode_stream(&compressed).is_err());
+        }
+    }
+}
+#[test]
+fn checksum_value_test() {
+    // Контрольные значения алгоритмов
+    let expected: [(Checksum, &[u8], Vec<u8>); 3] = [
+        (
+            Checksum::Crc32,
+            b"123456789",
+            0xcbf4_3926u32.to_le_bytes().to_vec(),
+        ),
+        (
+            Checksum::XxHash64,
+            b"",
+            0xef46_db37_51d8_e999u64.to_le_bytes().to_vec(),
+        ),
+        (
+            Checksum::Sha256,
+            b"abc",
+            vec![
+                0xba, 0x78, 0x16, 0xbf, 0x8f, 0x01, 0xcf, 0xea, 0x41, 0x41, 0x40, 0xde, 0x5d, 0xae,
+                0x22, 0x23, 0xb0, 0x03, 0x61, 0xa3, 0x96, 0x17, 0x7a, 0x9c, 0xb4, 0x10, 0xff, 0x61,
+                0xf2, 0x00, 0x15, 0xad,
+            ],
+        ),
+    ];
+    for (checksum, data, value) in &expected {
+        let compressed = compress_with(data, &with_checksum(*checksum));
+        assert!(compressed[compressed.len() - checksum.output_len()..] == value[..]);
+    }
+}
+#[test]
+fn checksum_mismatch_test() {
+    let source = &read("test-file").unwrap()[..100_000];
+    for &checksum in &CHECKSUMS {
+        // Поврежденная контрольная сумма обычного и блочного архива
+        for options in &[
+            with_checksum(checksum),
+            lzw::Options {
+                block_size: Some(30_000),
+                ..with_checksum(checksum)
+            },
+        ] {
+            let mut compressed = compress_with(source, options);
+            let position = match options.block_size {
+                // Контрольная сумма блочного архива лежит перед индексом
+                Some(_) => {
+                    let mut index_offset = [0u8; 8];
+                    index_offset.copy_from_slice(&compressed[compressed.len() - 12..][..8]);
+                    u64::from_le_bytes(index_offset) as usize - 1
+                }
+                None => compressed.len() - 1,
+            };
+            compressed[position] ^= 1;
+            match lzw::decompress_bytes(&compressed) {
+                Err(Error::ChecksumMismatch) => {}
+                result => panic!("{:?}", result.map(|data| data.len())),
+            }
+            match decode_stream(&compressed) {
+                Err(Error::ChecksumMismatch) => {}
+                result => panic!("{:?}", result.map(|data| data.len())),
+            }
+        }
+    }
+    // Оборванная контрольная сумма
+    let compressed = compress_with(&source[..10], &with_checksum(Checksum::Sha256));
+    assert!(lzw::decompress_bytes(&compressed[..compressed.len() - 30]).is_err());
+    // Неизвестный алгоритм
+    let mut compressed = compress_with(source, &with_checksum(Checksum::Crc32));
+    compressed[7] = 0xff;
+    match lzw::decompress_bytes(&compressed) {
+        Err(Error::BadHeader(_)) => {}
+        result => panic!("{:?}", result.map(|data| data.len())),
+    }
+    // Записи многофайлового архива
+    let mut writer = ArchiveWriter::new(Vec::new(), &with_checksum(Checksum::XxHash64)).unwrap();
+    writer.add("one", source).unwrap();
+    let archive = writer.finish().unwrap();
+    let mut reader = ArchiveReader::new(Cursor::new(archive)).unwrap();
+    assert_eq!(reader.header(0).unwrap().checksum, Some(Checksum::XxHash64));
+    let mut result = Vec::new();
+    reader.extract(0, &mut result).unwrap();
+    assert!(result == source);
+}
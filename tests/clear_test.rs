@@ -0,0 +1,55 @@
+mod common;
+
+use common::random_bytes;
+use lzw_arc::lzw::header::{Header, FLAG_CLEAR};
+use lzw_arc::lzw::{self, DictionaryMode, Error};
+use std::fs::read;
+
+fn compress_clear(data: &[u8], max_bits_count: usize) -> Vec<u8> {
+    let options = lzw::Options {
+        max_bits_count,
+        dictionary_mode: DictionaryMode::Clear,
+        ..Default::default()
+    };
+    let mut compressed = Vec::new();
+    lzw::compress_bytes_with(data, &options, &mut compressed).unwrap();
+    compressed
+}
+#[test]
+fn clear_round_trip_test() {
+    let source = &read("test-file").unwrap()[..200_000];
+    for bits in 9..=13 {
+        let compressed = compress_clear(source, bits);
+        let header = Header::read(&mut &compressed[..]).unwrap();
+        assert_eq!(header.flags & FLAG_CLEAR, FLAG_CLEAR);
+        assert!(lzw::decompress_bytes(&compressed).unwrap() == source);
+    }
+    // Характер данных меняется: словарь от первой части бесполезен
+    // для второй, степень сжатия падает, и словарь сбрасывается
+    let mut source = random_bytes(300_000, b'a', 3);
+    source.extend(random_bytes(300_000, 0, 200));
+    source.extend(random_bytes(300_000, b'x', 2));
+    let compressed = compress_clear(&source, 10);
+    assert!(lzw::decompress_bytes(&compressed).unwrap() == source);
+    // Пустой поток
+    let compressed = compress_clear(&[], 9);
+    assert!(lzw::decompress_bytes(&compressed).unwrap().is_empty());
+}
+#[test]
+fn clear_ratio_test() {
+    // Однородные данные: заполненный словарь продолжает работать
+    let source = random_bytes(500_000, 0, 4);
+    let reset = lzw::compress_bytes(&source, 12).unwrap();
+    let clear = compress_clear(&source, 12);
+    assert!(clear.len() < reset.len());
+}
+#[test]
+fn clear_header_test() {
+    // Режимы LZT и CLEAR несовместимы
+    let mut compressed = compress_clear(b"abracadabra", 9);
+    compressed[6] |= lzw::header::FLAG_LZT;
+    match lzw::decompress_bytes(&compressed) {
+        Err(Error::BadHeader(_)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
@@ -0,0 +1,125 @@
+use std::fs;
+use std::process::{Command, Output};
+
+fn lzw_arc(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_lzw_arc"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn list_and_test_cli_test() {
+    fs::create_dir_all("cli_test_src").unwrap();
+    fs::write("cli_test_src/one", b"one one one one").unwrap();
+    fs::write("cli_test_src/two", b"two two two two").unwrap();
+    let output = lzw_arc(&["a", "cli_test.lzw", "cli_test_src", "-b", "12"]);
+    fs::remove_dir_all("cli_test_src").unwrap();
+    assert!(output.status.success());
+
+    let output = lzw_arc(&["l", "cli_test.lzw"]);
+    assert!(output.status.success());
+    let listing = String::from_utf8(output.stdout).unwrap();
+    assert!(listing.contains("cli_test_src/one"));
+    assert!(listing.contains("cli_test_src/two"));
+    assert!(listing.contains("12  no"));
+
+    assert_eq!(lzw_arc(&["t", "cli_test.lzw"]).status.code(), Some(0));
+    // Повреждаем данные первого файла: проверка должна завершиться с кодом 2
+    let mut archive = fs::read("cli_test.lzw").unwrap();
+    archive[5 + 12] ^= 0xff;
+    fs::write("cli_test.lzw", &archive).unwrap();
+    let output = lzw_arc(&["t", "cli_test.lzw"]);
+    fs::remove_file("cli_test.lzw").unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stdout).unwrap().contains("FAILED"));
+
+    // Отсутствующий архив - ошибка ввода-вывода
+    assert_eq!(
+        lzw_arc(&["t", "cli_test_missing.lzw"]).status.code(),
+        Some(1)
+    );
+}
+#[test]
+fn self_exclude_cli_test() {
+    // Архив записывается в сжимаемый каталог и не должен читать сам себя
+    let dir = "cli_self_test";
+    fs::create_dir_all(dir).unwrap();
+    fs::write(format!("{}/one", dir), b"one one one one").unwrap();
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_lzw_arc"))
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap()
+    };
+    let archived = run(&["a", "out.lzw", "."]);
+    // Повторный запуск перезаписывает уже существующий архив
+    let rearchived = run(&["a", "out.lzw", "."]);
+    let listing = run(&["l", "out.lzw"]);
+    fs::remove_dir_all(dir).unwrap();
+    assert!(archived.status.success());
+    assert!(rearchived.status.success());
+    assert!(listing.status.success());
+    let listing = String::from_utf8(listing.stdout).unwrap();
+    assert!(listing.contains("one"));
+    assert!(!listing.contains("out.lzw"));
+}
+#[test]
+fn threads_cli_test() {
+    fs::create_dir_all("cli_threads_src").unwrap();
+    fs::write("cli_threads_src/one", b"one one one one").unwrap();
+    let output = lzw_arc(&["a", "cli_threads.lzw", "cli_threads_src", "-j", "100000"]);
+    fs::remove_dir_all("cli_threads_src").unwrap();
+    let _ = fs::remove_file("cli_threads.lzw");
+    // Слишком много потоков — ошибка параметра, а не аварийное завершение
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("256"));
+}
+#[test]
+fn stdio_cli_test() {
+    use std::io::Write;
+    use std::process::Stdio;
+    let pipe = |args: &[&str], input: &[u8]| -> Vec<u8> {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_lzw_arc"))
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        // Ввод подается из отдельного потока, иначе заполненные каналы
+        // заблокируют и тест, и архиватор
+        let mut stdin = child.stdin.take().unwrap();
+        let input = input.to_vec();
+        let feeder = std::thread::spawn(move || stdin.write_all(&input).unwrap());
+        let output = child.wait_with_output().unwrap();
+        feeder.join().unwrap();
+        assert!(output.status.success());
+        output.stdout
+    };
+    let source = fs::read("test-file").unwrap();
+    // Стандартный ввод сжимается в одиночный поток
+    let archive = pipe(&["a", "-", "-", "-b", "12"], &source);
+    assert!(archive == lzw_arc::lzw::compress_bytes(&source, 12).unwrap());
+    assert!(pipe(&["e", "-"], &archive) == source);
+    let archive = pipe(
+        &["a", "-", "-", "-p", "secret", "--kdf-iterations", "100"],
+        &source,
+    );
+    assert!(pipe(&["e", "-", "-", "-p", "secret"], &archive) == source); // Формат .Z определяется при распаковке по сигнатуре
+    let archive = pipe(&["a", "-", "-", "--format", "z"], &source);
+    assert_eq!(archive[..3], [0x1f, 0x9d, 0x90]);
+    assert!(pipe(&["e", "-"], &archive) == source);
+    // Блочный архив не зависит от количества потоков
+    let archive = pipe(&["a", "-", "-", "--threads", "4"], &source);
+    assert!(archive == pipe(&["a", "-", "-", "-j", "2"], &source));
+    assert!(pipe(&["e", "-", "-", "-j", "3"], &archive) == source);
+    assert!(pipe(&["e", "-"], &archive) == source);
+    // Контрольная сумма проверяется при распаковке
+    let archive = pipe(&["a", "-", "-", "--checksum", "xxhash64"], &source);
+    assert!(pipe(&["e", "-"], &archive) == source);
+    // Энтропийное кодирование сжимает сильнее
+    let compressed = pipe(&["a", "-", "-", "--entropy", "range"], &source);
+    assert!(compressed.len() < pipe(&["a", "-", "-"], &source).len());
+    assert!(pipe(&["e", "-"], &compressed) == source);
+}
@@ -0,0 +1,32 @@
+//! Вспомогательные функции, общие для интеграционных тестов
+// Каждый тест подключает модуль целиком, но использует лишь часть функций
+#![allow(dead_code)]
+
+use lzw_arc::lzw::{self, LzwDecoder};
+use std::io::Read;
+
+/// Псевдослучайные байты из алфавита first..first + alphabet.
+/// Последовательность всегда одна и та же
+pub fn random_bytes(len: usize, first: u8, alphabet: u32) -> Vec<u8> {
+    let mut state: u32 = 12345;
+    (0..len)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            first + ((state >> 16) % alphabet) as u8
+        })
+        .collect()
+}
+
+/// Сжимает данные в памяти с заданными параметрами
+pub fn compress_with(data: &[u8], options: &lzw::Options) -> Vec<u8> {
+    let mut compressed = Vec::new();
+    lzw::compress_bytes_with(data, options, &mut compressed).unwrap();
+    compressed
+}
+
+/// Распаковывает поток целиком через LzwDecoder
+pub fn decode_stream(compressed: &[u8]) -> lzw::Result<Vec<u8>> {
+    let mut result = Vec::new();
+    LzwDecoder::new(compressed)?.read_to_end(&mut result)?;
+    Ok(result)
+}
@@ -1,6 +1,8 @@
 use lzw_arc::lzw;
 use sha1::{Sha1, Digest};
+use std::env::temp_dir;
 use std::fs::{remove_file, File};
+use std::path::Path;
 
 #[test]
 fn compress_test() {
@@ -22,7 +24,7 @@ fn compress_test() {
 }
 #[test]
 fn decompress_test() {
-    lzw::decompress("test-compressed", "decompress_test", 16).unwrap();
+    lzw::decompress("test-compressed", "decompress_test").unwrap();
     // Source hash
     let mut file = File::open("test-file").unwrap();
     let mut hasher = Sha1::new();
@@ -40,7 +42,7 @@ fn decompress_test() {
 #[test]
 fn aes_test() {
     lzw::compress_aes("test-file", "aes_test", 16, "secret").unwrap();
-    lzw::decompress_aes("aes_test", "aes_test_result", 16, "secret").unwrap();
+    lzw::decompress_aes("aes_test", "aes_test_result", "secret").unwrap();
     // Source hash
     let mut file = File::open("test-file").unwrap();
     let mut hasher = Sha1::new();
@@ -55,4 +57,20 @@ fn aes_test() {
     remove_file("aes_test").unwrap();
     remove_file("aes_test_result").unwrap();
     assert_eq!(source_hash, result_hash);
-}
\ No newline at end of file
+}
+#[test]
+fn header_test() {
+    // Файл без заголовка не распаковывается, а файл результата не создается
+    let result_file = temp_dir().join("lzw_arc_header_test_result");
+    let result_file = result_file.to_str().unwrap();
+    match lzw::decompress("test-file", result_file) {
+        Err(lzw::Error::BadHeader(_)) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+    assert!(!Path::new(result_file).exists());
+    // Размер словаря читается из заголовка
+    let mut file = File::open("test-compressed").unwrap();
+    let header = lzw::header::Header::read(&mut file).unwrap();
+    assert_eq!(header.max_bits_count, 16);
+    assert!(!header.is_encrypted());
+}
@@ -0,0 +1,147 @@
+mod common;
+
+use common::{compress_with, decode_stream};
+use lzw_arc::lzw::archive::{ArchiveReader, ArchiveWriter};
+use lzw_arc::lzw::header::{Header, FLAG_ENTROPY};
+use lzw_arc::lzw::{self, Checksum, DictionaryMode, Entropy, Error, LzwEncoder};
+use lzw_arc::lzw::{Options, SeekableDecoder};
+use std::fs::read;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+const MODES: [DictionaryMode; 3] = [
+    DictionaryMode::Reset,
+    DictionaryMode::Clear,
+    DictionaryMode::Lzt,
+];
+
+fn with_entropy(max_bits_count: usize, mode: DictionaryMode) -> Options {
+    Options {
+        max_bits_count,
+        dictionary_mode: mode,
+        entropy: Some(Entropy::Range),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn entropy_round_trip_test() {
+    let source = &read("test-file").unwrap()[..200_000];
+    for &mode in &MODES {
+        for bits in 9..=16 {
+            let options = with_entropy(bits, mode);
+            let compressed = compress_with(source, &options);
+            let header = Header::read(&mut &compressed[..]).unwrap();
+            assert_eq!(header.flags & FLAG_ENTROPY, FLAG_ENTROPY);
+            assert_eq!(header.entropy, Some(Entropy::Range));
+            assert!(lzw::decompress_bytes(&compressed).unwrap() == source);
+            assert!(decode_stream(&compressed).unwrap() == source);
+        }
+    }
+    // Пустой поток и один байт
+    for data in &[&[][..], &b"a"[..]] {
+        let compressed = compress_with(data, &with_entropy(12, DictionaryMode::Reset));
+        assert!(lzw::decompress_bytes(&compressed).unwrap() == *data);
+        assert!(decode_stream(&compressed).unwrap() == *data);
+    }
+    // Потоковый кодировщик дает тот же архив
+    let options = with_entropy(12, DictionaryMode::Clear);
+    let compressed = compress_with(source, &options);
+    let mut encoder = LzwEncoder::with_options(Vec::new(), &options).unwrap();
+    for chunk in source.chunks(777) {
+        encoder.write_all(chunk).unwrap();
+    }
+    assert!(encoder.finish().unwrap() == compressed);
+    // Шифрование: коды расшифровываются по блоку AES
+    let options = Options {
+        password: Some("secret".to_owned()),
+        kdf_iterations: 1000,
+        checksum: Some(Checksum::Crc32),
+        ..with_entropy(12, DictionaryMode::Reset)
+    };
+    let compressed = compress_with(source, &options);
+    assert!(lzw::decompress_aes_bytes(&compressed, "secret").unwrap() == source);
+    // Контрольная сумма за кодами
+    let options = Options {
+        checksum: Some(Checksum::XxHash64),
+        ..with_entropy(16, DictionaryMode::Lzt)
+    };
+    let compressed = compress_with(source, &options);
+    assert!(lzw::decompress_bytes(&compressed).unwrap() == source);
+    assert!(decode_stream(&compressed).unwrap() == source);
+}
+#[test]
+fn entropy_ratio_test() {
+    let source = read("test-file").unwrap();
+    for &mode in &MODES {
+        let plain = compress_with(
+            &source,
+            &Options {
+                entropy: None,
+                ..with_entropy(16, mode)
+            },
+        );
+        let compressed = compress_with(&source, &with_entropy(16, mode));
+        assert!(compressed.len() < plain.len());
+    }
+}
+#[test]
+fn entropy_block_test() {
+    let source = &read("test-file").unwrap()[..300_000];
+    let options = Options {
+        threads: 3,
+        block_size: Some(40_000),
+        checksum: Some(Checksum::Sha256),
+        ..with_entropy(12, DictionaryMode::Reset)
+    };
+    let compressed = compress_with(source, &options);
+    let header = Header::read(&mut &compressed[..]).unwrap();
+    assert!(header.has_blocks());
+    assert_eq!(header.entropy, Some(Entropy::Range));
+    assert!(lzw::decompress_bytes(&compressed).unwrap() == source);
+    assert!(decode_stream(&compressed).unwrap() == source);
+    let mut result = Vec::new();
+    lzw::decompress_parallel(compressed.as_slice(), &mut result, 4).unwrap();
+    assert!(result == source);
+    // Перемещение возможно только к началу блока
+    let mut decoder = SeekableDecoder::new(Cursor::new(compressed)).unwrap();
+    assert_eq!(decoder.len(), source.len() as u64);
+    for &pos in &[0, 39_999, 40_000, 123_456, 299_000] {
+        decoder.seek(SeekFrom::Start(pos)).unwrap();
+        let mut buf = vec![0u8; 1000];
+        decoder.read_exact(&mut buf).unwrap();
+        assert!(buf[..] == source[pos as usize..pos as usize + 1000]);
+    }
+    // Многофайловый архив
+    let mut writer = ArchiveWriter::new(Vec::new(), &options).unwrap();
+    writer.add("one", source).unwrap();
+    let archive = writer.finish().unwrap();
+    let mut reader = ArchiveReader::new(Cursor::new(archive)).unwrap();
+    let mut result = Vec::new();
+    reader.extract(0, &mut result).unwrap();
+    assert!(result == source);
+}
+#[test]
+fn entropy_error_test() {
+    let source = &read("test-file").unwrap()[..50_000];
+    let compressed = compress_with(source, &with_entropy(12, DictionaryMode::Reset));
+    // Неизвестный кодер: идентификатор следует за основным заголовком
+    let mut corrupted = compressed.clone();
+    corrupted[7] = 0xFF;
+    match lzw::decompress_bytes(&corrupted) {
+        Err(Error::BadHeader(_)) => {}
+        result => panic!("{:?}", result.map(|data| data.len())),
+    }
+    // Потоковый кодировщик не шифрует и не сжимает блоками
+    let options = Options {
+        threads: 2,
+        ..with_entropy(12, DictionaryMode::Reset)
+    };
+    match LzwEncoder::with_options(Vec::new(), &options) {
+        Err(Error::InvalidOption(_)) => {}
+        result => panic!("{:?}", result.is_ok()),
+    }
+    // Поврежденное начало кодов
+    let mut corrupted = compressed;
+    corrupted[8] = 1;
+    assert!(lzw::decompress_bytes(&corrupted).is_err());
+}
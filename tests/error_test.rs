@@ -0,0 +1,139 @@
+use lzw_arc::lzw::{self, Error, LzwDecoder};
+use std::io::Read;
+
+#[test]
+fn unsupported_bits_test() {
+    for &bits in &[0, 8, 33, 264] {
+        match lzw::compress_bytes(b"data", bits) {
+            Err(Error::UnsupportedBits(value)) => assert_eq!(value, bits),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+}
+#[test]
+fn invalid_code_test() {
+    let mut archive = lzw::compress_bytes(b"abababababababab", 9).unwrap();
+    // Первое кодовое слово ссылается за пределы словаря
+    archive[7] = 0xff;
+    archive[8] = 0xff;
+    match lzw::decompress_bytes(&archive) {
+        Err(Error::InvalidCode(_)) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+    // Та же ошибка через Read оборачивается в io::Error
+    let mut decoder = LzwDecoder::new(archive.as_slice()).unwrap();
+    let err = decoder.read_to_end(&mut Vec::new()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+#[test]
+fn truncated_test() {
+    let archive = lzw::compress_bytes(b"data", 9).unwrap();
+    match lzw::decompress_bytes(&archive[..3]) {
+        Err(Error::Truncated) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+    let archive = lzw::compress_aes_bytes(b"data", 9, "secret").unwrap();
+    match lzw::decompress_aes_bytes(&archive[..archive.len() - 1], "secret") {
+        Err(Error::Truncated) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+}
+#[test]
+fn encryption_mismatch_test() {
+    let archive = lzw::compress_aes_bytes(b"data", 9, "secret").unwrap();
+    match lzw::decompress_bytes(&archive) {
+        Err(Error::PasswordRequired) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+    let archive = lzw::compress_bytes(b"data", 9).unwrap();
+    match lzw::decompress_aes_bytes(&archive, "secret") {
+        Err(Error::NotEncrypted) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+}
+#[test]
+fn tampered_archive_test() {
+    let archive = lzw::compress_aes_bytes(b"attack at dawn, attack at dawn", 9, "secret").unwrap();
+    // Изменение любого байта, включая заголовок и код аутентификации, обнаруживается
+    for &pos in &[5, 7, 23, archive.len() - 33, archive.len() - 1] {
+        let mut tampered = archive.clone();
+        tampered[pos] ^= 0x01;
+        let mut out = Vec::new();
+        match lzw::decompress_aes_bytes_into(&tampered, "secret", &mut out) {
+            Err(Error::AuthenticationFailed)
+            | Err(Error::UnsupportedBits(_))
+            | Err(Error::BadHeader(_))
+            | Err(Error::WrongPassword) => {}
+            result => panic!("unexpected result at {}: {:?}", pos, result),
+        }
+        // Ни одного байта открытого текста не выдано
+        assert!(out.is_empty());
+    }
+    // Файл результата не создается
+    std::fs::write("tampered_test", &archive[..archive.len() - 1]).unwrap();
+    assert!(lzw::decompress_aes("tampered_test", "tampered_test_result", "secret").is_err());
+    assert!(!std::path::Path::new("tampered_test_result").exists());
+    std::fs::remove_file("tampered_test").unwrap();
+}
+#[test]
+fn single_pass_aes_test() {
+    // Архив читается ровно один раз и без перемещения: проверяется
+    // тот же шифротекст, который расшифровывается
+    struct Counting<'a> {
+        data: &'a [u8],
+        count: usize,
+    }
+    impl<'a> Read for Counting<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = self.data.read(buf)?;
+            self.count += len;
+            Ok(len)
+        }
+    }
+    let archive = lzw::compress_aes_bytes(&[7u8; 10_000], 12, "secret").unwrap();
+    let mut reader = Counting {
+        data: &archive,
+        count: 0,
+    };
+    let mut out = Vec::new();
+    lzw::decompress_aes_stream(&mut reader, &mut out, "secret").unwrap();
+    assert!(out == vec![7u8; 10_000]);
+    assert_eq!(reader.count, archive.len());
+}
+#[test]
+fn invalid_kdf_test() {
+    let options = lzw::Options {
+        password: Some("secret".to_owned()),
+        kdf_iterations: 0,
+        ..Default::default()
+    };
+    match lzw::compress_bytes_with(b"data", &options, &mut Vec::new()) {
+        Err(Error::InvalidOption(_)) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+    // Неизвестный алгоритм получения ключа в заголовке
+    let mut archive = lzw::compress_aes_bytes(b"data", 9, "secret").unwrap();
+    archive[lzw::header::HEADER_LEN] = 0xff;
+    match lzw::decompress_aes_bytes(&archive, "secret") {
+        Err(Error::BadHeader(_)) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+}
+#[test]
+fn wrong_password_test() {
+    let archive = lzw::compress_aes_bytes(b"attack at dawn", 9, "secret").unwrap();
+    let mut out = Vec::new();
+    match lzw::decompress_aes_bytes_into(&archive, "wrong", &mut out) {
+        Err(Error::WrongPassword) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+    assert!(out.is_empty());
+    // Файл результата не создается
+    std::fs::write("wrong_password_test", &archive).unwrap();
+    match lzw::decompress_aes("wrong_password_test", "wrong_password_test_result", "wrong") {
+        Err(Error::WrongPassword) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+    assert!(!std::path::Path::new("wrong_password_test_result").exists());
+    std::fs::remove_file("wrong_password_test").unwrap();
+}
@@ -0,0 +1,102 @@
+use lzw_arc::lzw::gif::{self, GifDecoder, GifEncoder, SubBlockReader, SubBlockWriter};
+use lzw_arc::lzw::Error;
+use std::io::{Read, Write};
+
+// Изображение 10x10 из четырех цветов и его данные в файле GIF
+const ROWS: [&str; 10] = [
+    "1111122222",
+    "1111122222",
+    "1111122222",
+    "1110000222",
+    "1110000222",
+    "2220000111",
+    "2220000111",
+    "2222211111",
+    "2222211111",
+    "2222211111",
+];
+const IMAGE_DATA: [u8; 25] = [
+    0x02, 0x16, 0x8c, 0x2d, 0x99, 0x87, 0x2a, 0x1c, 0xdc, 0x33, 0xa0, 0x02, 0x75, 0xec, 0x95, 0xfa,
+    0xa8, 0xde, 0x60, 0x8c, 0x04, 0x91, 0x4c, 0x01, 0x00,
+];
+
+fn pixels() -> Vec<u8> {
+    ROWS.concat().bytes().map(|byte| byte - b'0').collect()
+}
+
+#[test]
+fn gif_known_vector_test() {
+    assert_eq!(
+        gif::encode_image_data(&pixels(), 2, Vec::new()).unwrap(),
+        &IMAGE_DATA[..]
+    );
+    // Данные после завершающего блока остаются в потоке
+    let mut data = IMAGE_DATA.to_vec();
+    data.push(0x3b);
+    let mut reader = &data[..];
+    let mut out = Vec::new();
+    gif::decode_image_data(&mut reader, &mut out).unwrap();
+    assert_eq!(out, pixels());
+    assert_eq!(reader, [0x3b]);
+}
+#[test]
+fn gif_round_trip_test() {
+    // Данные файла не помещаются в один словарь из 4096 кодов
+    let data = std::fs::read("test-file").unwrap();
+    let encoded = gif::encode_image_data(&data, 8, Vec::new()).unwrap();
+    let mut out = Vec::new();
+    gif::decode_image_data(&encoded[..], &mut out).unwrap();
+    assert!(out == data);
+    // Поток кодов без подблоков
+    let pixels: Vec<u8> = (0..20_000u32).map(|i| (i * i / 7 % 5) as u8).collect();
+    let mut encoder = GifEncoder::new(Vec::new(), 3).unwrap();
+    encoder.write_all(&pixels).unwrap();
+    let codes = encoder.finish().unwrap();
+    let mut out = Vec::new();
+    GifDecoder::new(&codes[..], 3)
+        .unwrap()
+        .read_to_end(&mut out)
+        .unwrap();
+    assert_eq!(out, pixels);
+}
+#[test]
+fn gif_sub_block_test() {
+    let data: Vec<u8> = (0..600u32).map(|i| i as u8).collect();
+    let mut writer = SubBlockWriter::new(Vec::new());
+    writer.write_all(&data).unwrap();
+    let blocks = writer.finish().unwrap();
+    assert_eq!(blocks.len(), 600 + 4);
+    assert_eq!((blocks[0], blocks[256], blocks[512]), (255, 255, 90));
+    assert_eq!(blocks[603], 0);
+    let mut out = Vec::new();
+    SubBlockReader::new(&blocks[..])
+        .read_to_end(&mut out)
+        .unwrap();
+    assert_eq!(out, data);
+    // Без завершающего блока
+    let err = SubBlockReader::new(&blocks[..603])
+        .read_to_end(&mut Vec::new())
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+}
+#[test]
+fn gif_errors_test() {
+    match gif::encode_image_data(&[0, 1, 4], 2, Vec::new()) {
+        Err(Error::InvalidSymbol(4)) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+    match GifEncoder::new(Vec::new(), 9) {
+        Err(Error::InvalidOption(_)) => {}
+        result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+    // Поток оборвался до EOI
+    match gif::decode_image_data(&IMAGE_DATA[..10], &mut Vec::new()) {
+        Err(Error::Truncated) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+    // За CLEAR следует код 6, а не литерал: строк в словаре еще нет
+    match gif::decode_image_data(&[0x02, 0x01, 0x34, 0x00][..], &mut Vec::new()) {
+        Err(Error::InvalidCode(6)) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+}
@@ -0,0 +1,66 @@
+use lzw_arc::lzw::lzc::{ZDecoder, ZEncoder};
+use lzw_arc::lzw::Error;
+use std::fs;
+use std::io::{Read, Write};
+
+fn compress(data: &[u8], bits: u8) -> Vec<u8> {
+    let mut encoder = ZEncoder::new(Vec::new(), bits).unwrap();
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+fn decompress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    ZDecoder::new(data).unwrap().read_to_end(&mut out).unwrap();
+    out
+}
+
+#[test]
+fn lzc_known_vector_test() {
+    // Поток, который `gzip -d` и `uncompress` распаковывают в ту же строку
+    let expected = [
+        0x1f, 0x9d, 0x89, 0x54, 0x9e, 0x08, 0x29, 0xf2, 0x44, 0x8a, 0x93, 0x27, 0x54, 0x02, 0x0e,
+        0x2c, 0xa8, 0x90, 0xa0, 0x41, 0x84,
+    ];
+    let data = b"TOBEORNOTTOBEORTOBEORNOT";
+    assert_eq!(compress(data, 9), &expected[..]);
+    assert_eq!(decompress(&expected), &data[..]);
+}
+#[test]
+fn lzc_round_trip_test() {
+    let source = fs::read("test-file").unwrap();
+    // Несжимаемый хвост после заполнения словаря приводит к CLEAR
+    let mut data = source.clone();
+    let mut state = 1u32;
+    data.extend((0..50_000).map(|_| {
+        state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        (state >> 16) as u8
+    }));
+    data.extend_from_slice(&source);
+    for &bits in &[9, 12, 16] {
+        let archive = compress(&data, bits);
+        assert_eq!(archive[2], 0x80 | bits);
+        assert!(decompress(&archive) == data);
+    }
+    // Пустой поток состоит из одного заголовка
+    assert_eq!(compress(b"", 16), [0x1f, 0x9d, 0x90]);
+    assert!(decompress(&[0x1f, 0x9d, 0x90]).is_empty());
+}
+#[test]
+fn lzc_errors_test() {
+    match ZEncoder::new(Vec::new(), 17) {
+        Err(Error::InvalidOption(_)) => {}
+        result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+    for header in &[&[0x1f, 0x8b, 0x90][..], &[0x1f, 0x9d, 0x91][..]] {
+        match ZDecoder::new(*header) {
+            Err(Error::BadHeader(_)) => {}
+            result => panic!("unexpected result: {:?}", result.map(|_| ())),
+        }
+    }
+    // Первый код ссылается за пределы словаря
+    let err = ZDecoder::new(&[0x1f, 0x9d, 0x90, 0xff, 0x01][..])
+        .unwrap()
+        .read_to_end(&mut Vec::new())
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use common::random_bytes;
+use lzw_arc::lzw::header::{Header, FLAG_LZT};
+use lzw_arc::lzw::{self, DictionaryMode, LzwDecoder, LzwEncoder};
+use std::fs::read;
+use std::io::{Read, Write};
+
+fn lzt(max_bits_count: usize) -> lzw::Options {
+    lzw::Options {
+        max_bits_count,
+        dictionary_mode: DictionaryMode::Lzt,
+        ..Default::default()
+    }
+}
+fn compress_lzt(data: &[u8], max_bits_count: usize) -> Vec<u8> {
+    let mut compressed = Vec::new();
+    lzw::compress_bytes_with(data, &lzt(max_bits_count), &mut compressed).unwrap();
+    compressed
+}
+#[test]
+fn lzt_round_trip_test() {
+    // Маленький словарь заполняется почти сразу, и строки постоянно вытесняются
+    let source = &read("test-file").unwrap()[..200_000];
+    for bits in 9..=13 {
+        let compressed = compress_lzt(source, bits);
+        let header = Header::read(&mut &compressed[..]).unwrap();
+        assert_eq!(header.flags & FLAG_LZT, FLAG_LZT);
+        assert!(lzw::decompress_bytes(&compressed).unwrap() == source);
+    }
+    let source = random_bytes(100_000, 0, 7);
+    let compressed = compress_lzt(&source, 9);
+    assert!(lzw::decompress_bytes(&compressed).unwrap() == source);
+    // Пустой поток
+    let compressed = compress_lzt(&[], 9);
+    assert!(lzw::decompress_bytes(&compressed).unwrap().is_empty());
+}
+#[test]
+fn lzt_single_chain_test() {
+    // Все строки словаря образуют одну цепочку a, aa, aaa...: единственный
+    // лист служит префиксом новой строки, и вытеснять нечего
+    let source = vec![b'a'; 300_000];
+    let compressed = compress_lzt(&source, 9);
+    assert!(lzw::decompress_bytes(&compressed).unwrap() == source);
+}
+#[test]
+fn lzt_ratio_test() {
+    // Однородные данные: после заполнения словарь продолжает работать,
+    // а не начинается заново
+    let source = random_bytes(500_000, 0, 4);
+    let reset = lzw::compress_bytes(&source, 12).unwrap();
+    let lzt = compress_lzt(&source, 12);
+    assert!(lzt.len() < reset.len());
+    // Режим по умолчанию не меняется
+    let source = read("test-file").unwrap();
+    let compressed = lzw::compress_bytes(&source, 16).unwrap();
+    assert!(compressed == read("test-compressed").unwrap());
+}
+#[test]
+fn lzt_stream_test() {
+    let source = &read("test-file").unwrap()[..100_000];
+    let mut encoder = LzwEncoder::with_mode(Vec::new(), 10, DictionaryMode::Lzt).unwrap();
+    for chunk in source.chunks(777) {
+        encoder.write_all(chunk).unwrap();
+    }
+    let compressed = encoder.finish().unwrap();
+    assert!(compressed == compress_lzt(source, 10));
+    let mut decoder = LzwDecoder::new(compressed.as_slice()).unwrap();
+    let mut result = Vec::new();
+    decoder.read_to_end(&mut result).unwrap();
+    assert!(result == source);
+    // Шифрование
+    let options = lzw::Options {
+        password: Some("secret".to_owned()),
+        kdf_iterations: 1000,
+        ..lzt(10)
+    };
+    let mut compressed = Vec::new();
+    lzw::compress_bytes_with(source, &options, &mut compressed).unwrap();
+    assert!(lzw::decompress_aes_bytes(&compressed, "secret").unwrap() == source);
+}
@@ -0,0 +1,102 @@
+use lzw_arc::lzw::{self, DictionaryMode, Error, SeekableDecoder};
+use std::fs::read;
+use std::io::{Cursor, ErrorKind, Read, Seek, SeekFrom};
+
+fn compress_blocks(data: &[u8], mode: DictionaryMode, block_size: usize) -> Vec<u8> {
+    let options = lzw::Options {
+        max_bits_count: 10,
+        dictionary_mode: mode,
+        threads: 2,
+        block_size: Some(block_size),
+        ..Default::default()
+    };
+    let mut compressed = Vec::new();
+    lzw::compress_bytes_with(data, &options, &mut compressed).unwrap();
+    compressed
+}
+fn read_at<R: Read + Seek>(decoder: &mut R, pos: u64, len: usize) -> Vec<u8> {
+    decoder.seek(SeekFrom::Start(pos)).unwrap();
+    let mut result = Vec::new();
+    decoder.take(len as u64).read_to_end(&mut result).unwrap();
+    result
+}
+
+#[test]
+fn seek_random_test() {
+    let source = &read("test-file").unwrap()[..300_000];
+    // Словарь 10 бит сбрасывается много раз внутри каждого блока
+    for &mode in &[
+        DictionaryMode::Reset,
+        DictionaryMode::Clear,
+        DictionaryMode::Lzt,
+    ] {
+        let compressed = compress_blocks(source, mode, 70_000);
+        let mut decoder = SeekableDecoder::new(Cursor::new(compressed)).unwrap();
+        assert_eq!(decoder.len(), source.len() as u64);
+        let mut state: u32 = 12345;
+        for _ in 0..200 {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let pos = (state >> 8) as usize % source.len();
+            let len = (state % 5000) as usize;
+            let end = (pos + len).min(source.len());
+            assert!(read_at(&mut decoder, pos as u64, len) == source[pos..end]);
+        }
+        // Чтение подряд без перемещений
+        let mut result = Vec::new();
+        decoder.seek(SeekFrom::Start(0)).unwrap();
+        decoder.read_to_end(&mut result).unwrap();
+        assert!(result == source);
+    }
+}
+#[test]
+fn seek_from_test() {
+    let source = &read("test-file").unwrap()[..100_000];
+    let compressed = compress_blocks(source, DictionaryMode::Reset, 30_000);
+    let mut decoder = SeekableDecoder::new(Cursor::new(compressed)).unwrap();
+    let mut buf = [0u8; 100];
+    assert_eq!(decoder.seek(SeekFrom::End(-100)).unwrap(), 99_900);
+    decoder.read_exact(&mut buf).unwrap();
+    assert!(buf[..] == source[99_900..]);
+    assert_eq!(decoder.seek(SeekFrom::Current(-50_000)).unwrap(), 50_000);
+    decoder.read_exact(&mut buf).unwrap();
+    assert!(buf[..] == source[50_000..50_100]);
+    // За концом данных позиция сохраняется, но читать нечего
+    assert_eq!(decoder.seek(SeekFrom::End(10)).unwrap(), 100_010);
+    assert_eq!(decoder.read(&mut buf).unwrap(), 0);
+    assert_eq!(decoder.seek(SeekFrom::Current(-10)).unwrap(), 100_000);
+    assert_eq!(decoder.seek(SeekFrom::Start(u64::MAX)).unwrap(), u64::MAX);
+    assert_eq!(decoder.read(&mut buf).unwrap(), 0);
+    assert!(decoder.seek(SeekFrom::Current(1)).is_err());
+    // Обратно от позиции за концом данных
+    assert_eq!(decoder.seek(SeekFrom::Start(100_500)).unwrap(), 100_500);
+    assert_eq!(decoder.seek(SeekFrom::Current(-600)).unwrap(), 99_900);
+    decoder.read_exact(&mut buf).unwrap();
+    assert!(buf[..] == source[99_900..]);
+    match decoder.seek(SeekFrom::Current(-200_000)) {
+        Err(err) => assert_eq!(err.kind(), ErrorKind::InvalidInput),
+        result => panic!("{:?}", result),
+    }
+    // Пустой архив
+    let compressed = compress_blocks(&[], DictionaryMode::Reset, 30_000);
+    let mut decoder = SeekableDecoder::new(Cursor::new(compressed)).unwrap();
+    assert!(decoder.is_empty());
+    assert_eq!(decoder.read(&mut buf).unwrap(), 0);
+}
+#[test]
+fn seek_error_test() {
+    let source = &read("test-file").unwrap()[..100_000];
+    // Обычный архив не содержит индекса
+    let compressed = lzw::compress_bytes(source, 12).unwrap();
+    match SeekableDecoder::new(Cursor::new(compressed)) {
+        Err(Error::BadHeader(_)) => {}
+        result => panic!("{:?}", result.map(|decoder| decoder.len())),
+    }
+    // Поврежденный концевик
+    let mut compressed = compress_blocks(source, DictionaryMode::Reset, 30_000);
+    let len = compressed.len();
+    compressed[len - 1] ^= 1;
+    match SeekableDecoder::new(Cursor::new(compressed)) {
+        Err(Error::BadHeader(_)) => {}
+        result => panic!("{:?}", result.map(|decoder| decoder.len())),
+    }
+}
@@ -0,0 +1,54 @@
+use lzw_arc::lzw::shrink::{self, ShrinkDecoder};
+use lzw_arc::lzw::Error;
+use std::io::Read;
+
+#[test]
+fn shrink_known_vector_test() {
+    // unzip от Info-ZIP распаковывает эти данные в ту же строку
+    let data = b"abababababababababab";
+    let shrunk = [0x61, 0xc4, 0x04, 0x1c, 0x28, 0xb0, 0x20, 0x41, 0x82];
+    assert_eq!(shrink::shrink(data).unwrap(), shrunk);
+    assert_eq!(shrink::unshrink(&shrunk).unwrap(), &data[..]);
+    assert!(shrink::shrink(b"").unwrap().is_empty());
+}
+#[test]
+fn shrink_round_trip_test() {
+    // Смесь сжимаемых и слабо сжимаемых данных заполняет словарь
+    // и приводит к частичным сбросам
+    let mut data = std::fs::read("test-file").unwrap();
+    let mut state = 7u32;
+    data.extend((0..300_000).map(|_| {
+        state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        (state >> 16) as u8 % 40
+    }));
+    let shrunk = shrink::shrink(&data).unwrap();
+    // Декодер читает ровно столько, сколько ему отдано
+    let mut out = Vec::new();
+    ShrinkDecoder::new((&shrunk[..]).take(shrunk.len() as u64))
+        .read_to_end(&mut out)
+        .unwrap();
+    assert!(out == data);
+}
+#[test]
+fn shrink_invalid_code_test() {
+    // Код 300 еще не назначен
+    match shrink::unshrink(&[0x61, 0x58, 0x02]) {
+        Err(Error::InvalidCode(300)) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+    // Неизвестный подкод после управляющего кода 256
+    match shrink::unshrink(&[0x61, 0x00, 0x0e, 0x00]) {
+        Err(Error::InvalidCode(3)) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+}
+#[test]
+fn shrink_reused_prefix_test() {
+    // Коды a b c 258, частичный сброс освобождает 257..=259, затем d e 257.
+    // Строка 257 получает префикс 258, освобожденный сбросом; когда код 258
+    // достается строке "de", строка 257 становится "ded", как в unzip
+    let data = [
+        0x61, 0xc4, 0x8c, 0x11, 0x08, 0x50, 0x00, 0x99, 0x32, 0x01, 0x01,
+    ];
+    assert_eq!(shrink::unshrink(&data).unwrap(), b"abcbcdeded");
+}
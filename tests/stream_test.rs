@@ -0,0 +1,55 @@
+use lzw_arc::lzw::{LzwDecoder, LzwEncoder};
+use std::fs::File;
+use std::io::{Read, Write};
+
+#[test]
+fn encoder_test() {
+    let mut source = Vec::new();
+    File::open("test-file")
+        .unwrap()
+        .read_to_end(&mut source)
+        .unwrap();
+    let mut expected = Vec::new();
+    File::open("test-compressed")
+        .unwrap()
+        .read_to_end(&mut expected)
+        .unwrap();
+    // Пишем неровными частями, результат не должен зависеть от разбиения
+    let mut encoder = LzwEncoder::new(Vec::new(), 16).unwrap();
+    for chunk in source.chunks(1000) {
+        encoder.write_all(chunk).unwrap();
+    }
+    let result = encoder.finish().unwrap();
+    assert!(result == expected);
+}
+#[test]
+fn decoder_test() {
+    let mut expected = Vec::new();
+    File::open("test-file")
+        .unwrap()
+        .read_to_end(&mut expected)
+        .unwrap();
+    let mut decoder = LzwDecoder::new(File::open("test-compressed").unwrap()).unwrap();
+    assert_eq!(decoder.header().max_bits_count, 16);
+    let mut result = Vec::new();
+    decoder.read_to_end(&mut result).unwrap();
+    assert!(result == expected);
+}
+#[test]
+fn round_trip_test() {
+    let source: Vec<u8> = b"TOBEORNOTTOBEORTOBEORNOT"
+        .iter()
+        .cycle()
+        .take(10_000)
+        .cloned()
+        .collect();
+    let mut encoder = LzwEncoder::new(Vec::new(), 9).unwrap();
+    encoder.write_all(&source).unwrap();
+    let compressed = encoder.finish().unwrap();
+    assert!(compressed.len() < source.len());
+
+    let mut decoder = LzwDecoder::new(compressed.as_slice()).unwrap();
+    let mut result = Vec::new();
+    decoder.read_to_end(&mut result).unwrap();
+    assert_eq!(result, source);
+}
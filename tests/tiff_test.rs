@@ -0,0 +1,74 @@
+use lzw_arc::lzw::bits::{BitOrder, BitWriter};
+use lzw_arc::lzw::tiff::{self, Config, TiffDecoder};
+use lzw_arc::lzw::Error;
+use std::io::Read;
+
+#[test]
+fn tiff_known_vector_test() {
+    // Пример фильтра LZWDecode из спецификации PDF:
+    // коды 256 45 258 258 65 259 66 257
+    let data = b"-----A---B";
+    let encoded = [0x80, 0x0b, 0x60, 0x50, 0x22, 0x0c, 0x0c, 0x85, 0x01];
+    assert_eq!(tiff::encode(data, Config::TIFF).unwrap(), encoded);
+    assert_eq!(tiff::decode(&encoded, Config::TIFF).unwrap(), &data[..]);
+}
+#[test]
+fn tiff_late_change_test() {
+    // Байты 0..=255 и еще один 0 дают коды 256, 0..=255, 0, 257 и строки
+    // 258..=513. С EarlyChange 1 разрядность растет до 10 бит перед кодом 254,
+    // когда следующей строкой станет 511; с EarlyChange 0 — перед кодом 255,
+    // когда следующей станет 512
+    let data: Vec<u8> = (0..=255).chain(Some(0)).collect();
+    for &(config, first_wide) in &[(Config::TIFF, 254), (Config::LATE_CHANGE, 255)] {
+        let mut expected = Vec::new();
+        let mut bits = BitWriter::new(BitOrder::Msb);
+        bits.write_bits(&mut expected, 256, 9).unwrap();
+        for (i, code) in (0..=255).chain(vec![0, 257]).enumerate() {
+            let width = if i >= first_wide { 10 } else { 9 };
+            bits.write_bits(&mut expected, code, width).unwrap();
+        }
+        bits.pad(&mut expected).unwrap();
+        assert_eq!(tiff::encode(&data, config).unwrap(), expected);
+        assert_eq!(tiff::decode(&expected, config).unwrap(), data);
+    }
+}
+#[test]
+fn tiff_round_trip_test() {
+    let data = std::fs::read("test-file").unwrap();
+    for &early_change in &[true, false] {
+        let config = Config { early_change };
+        let encoded = tiff::encode(&data, config).unwrap();
+        assert!(tiff::decode(&encoded, config).unwrap() == data);
+        // Поток без EOD тоже распаковывается
+        let mut out = Vec::new();
+        TiffDecoder::new(&encoded[..encoded.len() - 2], config)
+            .read_to_end(&mut out)
+            .unwrap();
+        assert!(data.starts_with(&out));
+        // EOD записывается той же разрядностью, которой его ждет декодер,
+        // в том числе сразу после ее смены
+        for len in 0..3000 {
+            let encoded = tiff::encode(&data[..len], config).unwrap();
+            assert!(tiff::decode(&encoded, config).unwrap() == data[..len]);
+        }
+    }
+}
+#[test]
+fn tiff_invalid_code_test() {
+    // Clear и код 300, которого еще нет в словаре
+    match tiff::decode(&[0x80, 0x4b, 0x00], Config::TIFF) {
+        Err(Error::InvalidCode(300)) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+    // После Clear код 258 еще не может быть строкой KwKwK: прошлого кода нет
+    let mut data = Vec::new();
+    let mut bits = BitWriter::new(BitOrder::Msb);
+    for &code in &[256, 65, 256, 258] {
+        bits.write_bits(&mut data, code, 9).unwrap();
+    }
+    bits.pad(&mut data).unwrap();
+    match tiff::decode(&data, Config::TIFF) {
+        Err(Error::InvalidCode(258)) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+}